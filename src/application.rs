@@ -1,13 +1,19 @@
 use {
     crate::{
         collection::{self, Collection},
+        compact::{self, CollUidMap},
         db::{Db, FolderChanges},
-        entry, serialization,
+        entry,
+        folder_scan::ChangesScan,
+        serialization,
     },
     anyhow::{bail, Context},
     std::path::{Path, PathBuf},
 };
 
+/// Name of the backup saved in the data folder before compacting Uids
+const COMPACT_BACKUP_FILENAME: &str = "before-compaction-backup.zip";
+
 type ActiveCollection = Option<(collection::Id, Collection)>;
 
 pub struct Application {
@@ -31,36 +37,52 @@ impl Application {
         self.database.recent.use_(id);
         id
     }
-    pub(crate) fn load_last(&mut self) -> anyhow::Result<FolderChanges> {
+    /// Add a collection and save it, without making it active
+    pub(crate) fn add_inactive_collection(
+        &mut self,
+        collection: &Collection,
+        root: PathBuf,
+    ) -> anyhow::Result<collection::Id> {
+        let id = self.database.insert_collection(root);
+        self.save_collection(id, collection)?;
+        Ok(id)
+    }
+    pub(crate) fn load_last(&mut self) -> anyhow::Result<Option<ChangesScan>> {
         if let Some(&id) = self.database.recent.most_recent() {
             self.load_collection(id)
+                .map(Some)
                 .with_context(|| format!("Error loading collection {id:?}"))
         } else {
-            Ok(FolderChanges::default())
+            Ok(None)
         }
     }
-    pub(crate) fn reload_active_collection(&mut self) -> anyhow::Result<FolderChanges> {
+    pub(crate) fn reload_active_collection(&mut self) -> anyhow::Result<ChangesScan> {
         if let Some((id, _)) = self.active_collection {
             self.load_collection(id)
         } else {
             bail!("No active collection")
         }
     }
-    pub(crate) fn load_collection(&mut self, id: collection::Id) -> anyhow::Result<FolderChanges> {
+    /// Load a collection, and start scanning its folder for changes in the background
+    pub(crate) fn load_collection(&mut self, id: collection::Id) -> anyhow::Result<ChangesScan> {
         self.save_active_collection()?;
-        let path = &self
+        let path = self
             .database
             .collections
             .get(&id)
             .context("No collection with such id")?;
-        let coll_dir = collections_dir_name(&self.database.data_dir);
-        let filename = collection_filename(&coll_dir, id);
-        let coll: Collection = serialization::read_from_file(&filename)
-            .with_context(move || format!("Deserialization error for: {}", filename.display()))?;
-        let changes = coll.scan_changes(path)?;
+        let coll = self.read_collection(id)?;
+        let scan = coll.start_changes_scan(id, path.clone());
         self.active_collection = Some((id, coll));
         self.database.recent.use_(id);
-        Ok(changes)
+        Ok(scan)
+    }
+    /// Read a collection from disk, without making it active
+    pub(crate) fn read_collection(&self, id: collection::Id) -> anyhow::Result<Collection> {
+        let coll_dir = collections_dir_name(&self.database.data_dir);
+        let filename = collection_filename(&coll_dir, id);
+        serialization::read_from_file(&filename)
+            .with_context(move || format!("Deserialization error for: {}", filename.display()))
     }
     pub(crate) fn apply_changes_to_active_collection(
         &mut self,
@@ -87,7 +109,128 @@ impl Application {
         self.active_collection = coll;
         Ok(())
     }
-    fn save_collection(&self, id: collection::Id, collection: &Collection) -> anyhow::Result<()> {
+    /// Renumber all Uids densely, see [`crate::compact`].
+    ///
+    /// A backup of everything is saved in the data folder first, and restored if the
+    /// compacted files can't all be put in place.
+    /// The active collection is reloaded with its new id.
+    ///
+    /// Returns the Uid mappings of the active collection's items, and the changes scan
+    /// of the reloaded collection.
+    pub(crate) fn compact_uids(&mut self) -> anyhow::Result<Option<(CollUidMap, ChangesScan)>> {
+        self.save_active_collection()?;
+        let active_root = self
+            .active_collection
+            .as_ref()
+            .and_then(|(id, _)| self.database.collections.get(id).cloned());
+        let mut colls = Vec::new();
+        for &id in self.database.collections.keys() {
+            colls.push((id, self.read_collection(id)?));
+        }
+        let backup_path = self.database.data_dir.join(COMPACT_BACKUP_FILENAME);
+        self.database
+            .save_backups(&backup_path)
+            .context("Failed to save backup")?;
+        let old_ids: Vec<collection::Id> = colls.iter().map(|(id, _)| *id).collect();
+        let mut maps = compact::compact(&mut self.database, &mut colls);
+        if let Err(e) = self.swap_in_compacted(&colls, &old_ids) {
+            let new_ids: Vec<collection::Id> = colls.iter().map(|(id, _)| *id).collect();
+            let undo_result = self.undo_compaction(&backup_path, &old_ids, &new_ids);
+            return Err(match undo_result {
+                Ok(()) => e.context("Compaction failed, everything was restored from the backup"),
+                Err(restore_err) => {
+                    // Can't tell which ids are valid on disk now, so don't risk saving over them
+                    self.active_collection = None;
+                    e.context(format!(
+                        "Compaction failed, and restoring the backup at {} failed too: \
+                         {restore_err:?}",
+                        backup_path.display()
+                    ))
+                }
+            });
+        }
+        // It has stale ids now, and must not be saved over the compacted one
+        self.active_collection = None;
+        let Some(new_id) =
+            active_root.and_then(|root| self.database.find_collection_by_path(&root))
+        else {
+            return Ok(None);
+        };
+        let scan = self.load_collection(new_id)?;
+        Ok(Some((maps.remove(&new_id).unwrap_or_default(), scan)))
+    }
+    /// Write out the compacted collections and database, then move them over the old ones.
+    ///
+    /// The old database stays in place until all the collection files are.
+    fn swap_in_compacted(
+        &self,
+        colls: &[(collection::Id, Collection)],
+        old_ids: &[collection::Id],
+    ) -> anyhow::Result<()> {
+        // New ids can be old ids of other collections, so write everything out of the way first
+        let dir_name = collections_dir_name(&self.database.data_dir);
+        for (id, coll) in colls {
+            serialization::write_to_file(coll, compacting_filename(&dir_name, *id))?;
+        }
+        let db_path = self.database.path();
+        let db_compacting_path = compacting_db_filename(&db_path);
+        serialization::write_to_file(&self.database, &db_compacting_path)?;
+        for (id, _) in colls {
+            std::fs::rename(
+                compacting_filename(&dir_name, *id),
+                collection_filename(&dir_name, *id),
+            )?;
+        }
+        for id in old_ids {
+            if !colls.iter().any(|(new_id, _)| new_id == id) {
+                std::fs::remove_file(collection_filename(&dir_name, *id))?;
+            }
+        }
+        std::fs::rename(db_compacting_path, db_path)?;
+        Ok(())
+    }
+    /// Put back the database and collection files from the backup taken before compacting,
+    /// and clean up what compaction left behind.
+    ///
+    /// The active collection is untouched by compaction, so it stays valid.
+    fn undo_compaction(
+        &mut self,
+        backup_path: &Path,
+        old_ids: &[collection::Id],
+        new_ids: &[collection::Id],
+    ) -> anyhow::Result<()> {
+        let dir_name = collections_dir_name(&self.database.data_dir);
+        // These may or may not exist, depending on how far compaction got
+        for id in new_ids {
+            let _ = std::fs::remove_file(compacting_filename(&dir_name, *id));
+            if !old_ids.contains(id) {
+                let _ = std::fs::remove_file(collection_filename(&dir_name, *id));
+            }
+        }
+        let _ = std::fs::remove_file(compacting_db_filename(&self.database.path()));
+        self.database.restore_backups_from(backup_path)
+    }
+    /// Remove a collection that isn't active from the database, deleting its collection file
+    pub(crate) fn remove_collection(&mut self, id: collection::Id) -> anyhow::Result<()> {
+        if self
+            .active_collection
+            .as_ref()
+            .is_some_and(|(active_id, _)| *active_id == id)
+        {
+            bail!("Can't remove the active collection");
+        }
+        self.database.collections.remove(&id);
+        self.database.recent.remove(id);
+        self.database.save()?;
+        let dir_name = collections_dir_name(&self.database.data_dir);
+        std::fs::remove_file(collection_filename(&dir_name, id))?;
+        Ok(())
+    }
+    pub(crate) fn save_collection(
+        &self,
+        id: collection::Id,
+        collection: &Collection,
+    ) -> anyhow::Result<()> {
         let dir_name = collections_dir_name(&self.database.data_dir);
         std::fs::create_dir_all(&dir_name)?;
         serialization::write_to_file(collection, collection_filename(&dir_name, id))
@@ -98,6 +241,18 @@ fn collections_dir_name(data_dir: &Path) -> PathBuf {
     data_dir.join("collections")
 }
 
+/// Where a collection is written during compaction, before it takes its final filename
+fn compacting_filename(collections_dir: &Path, id: collection::Id) -> PathBuf {
+    collections_dir.join(format!("{}.db.compacting", id.0))
+}
+
+/// Where the database is written during compaction, before it replaces the old one
+fn compacting_db_filename(db_path: &Path) -> PathBuf {
+    let mut name = db_path.as_os_str().to_owned();
+    name.push(".compacting");
+    name.into()
+}
+
 fn collection_filename(collections_dir: &Path, id: collection::Id) -> PathBuf {
     collections_dir.join(format!("{}.db", id.0))
 }
@@ -0,0 +1,139 @@
+//! Images inside ZIP/CBZ archives, as virtual entries.
+//!
+//! A member of an archive is addressed by the path of the archive, [`SEPARATOR`], and the
+//! name of the member inside the archive, like `comics/issue1.cbz!/page01.png`.
+
+use {
+    crate::file_type::{self, FileType},
+    std::{
+        fs::File,
+        io::{self, Read},
+        path::{Path, PathBuf},
+        time::SystemTime,
+    },
+    zip::ZipArchive,
+};
+
+/// Separates the archive path from the member name in virtual paths
+pub const SEPARATOR: &str = "!/";
+
+/// Whether `path` is an archive whose images can be browsed, judging by the extension
+pub fn is_expandable(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip") || ext.eq_ignore_ascii_case("cbz"))
+}
+
+/// Split a virtual path into the archive path and the member name.
+///
+/// `None` if `path` doesn't point inside an archive.
+pub fn split_member(path: &Path) -> Option<(&Path, &str)> {
+    let s = path.to_str()?;
+    s.match_indices(SEPARATOR).find_map(|(idx, _)| {
+        let archive = Path::new(&s[..idx]);
+        is_expandable(archive).then(|| (archive, &s[idx + SEPARATOR.len()..]))
+    })
+}
+
+pub fn member_path(archive: &Path, member: &str) -> PathBuf {
+    let mut s = archive.as_os_str().to_owned();
+    s.push(SEPARATOR);
+    s.push(member);
+    s.into()
+}
+
+/// The path of the file on disk that holds `path`: the archive for members, `path` otherwise
+pub fn on_disk_path(path: &Path) -> &Path {
+    split_member(path).map_or(path, |(archive, _)| archive)
+}
+
+/// Modification time of the file at `path`, `None` if it can't be determined.
+///
+/// For archive members, this is the modification time of the archive.
+pub fn mtime(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(on_disk_path(path))
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+/// Names of the image members of the archive at `path`, in order
+pub fn image_members(path: &Path) -> io::Result<Vec<String>> {
+    let zip = ZipArchive::new(File::open(path)?).map_err(io::Error::other)?;
+    let mut members: Vec<String> = zip
+        .file_names()
+        .filter(|name| {
+            !name.ends_with('/')
+                && file_type::by_extension(Path::new(name)) == Some(FileType::Image)
+        })
+        .map(str::to_owned)
+        .collect();
+    members.sort();
+    Ok(members)
+}
+
+/// Virtual paths of the image members of the archive at `archive`.
+///
+/// `archive` is relative to the collection root, and `root` is the root.
+pub fn expand(root: &Path, archive: &Path) -> io::Result<Vec<PathBuf>> {
+    Ok(image_members(&root.join(archive))?
+        .iter()
+        .map(|member| member_path(archive, member))
+        .collect())
+}
+
+/// Read the whole file of an entry, extracting it if it's an archive member
+pub fn read(path: &Path) -> io::Result<Vec<u8>> {
+    let Some((archive, member)) = split_member(path) else {
+        return std::fs::read(path);
+    };
+    let mut zip = ZipArchive::new(File::open(archive)?).map_err(io::Error::other)?;
+    let mut file = zip.by_name(member).map_err(io::Error::other)?;
+    let mut data = Vec::with_capacity(file.size().try_into().unwrap_or(0));
+    file.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{member_path, on_disk_path, split_member},
+        std::path::Path,
+    };
+
+    #[test]
+    fn split() {
+        assert_eq!(
+            split_member(Path::new("comics/issue1.cbz!/page01.png")),
+            Some((Path::new("comics/issue1.cbz"), "page01.png"))
+        );
+        assert_eq!(
+            split_member(Path::new("a.ZIP!/dir/b.png")),
+            Some((Path::new("a.ZIP"), "dir/b.png"))
+        );
+        assert_eq!(split_member(Path::new("comics/page01.png")), None);
+        // Not an archive, even though the separator appears
+        assert_eq!(split_member(Path::new("wow!/page01.png")), None);
+        assert_eq!(split_member(Path::new("a.rar!/b.png")), None);
+    }
+
+    #[test]
+    fn split_picks_the_archive_separator() {
+        // The first separator doesn't follow an archive name, the second one does
+        assert_eq!(
+            split_member(Path::new("yes!/a.cbz!/b!/c.png")),
+            Some((Path::new("yes!/a.cbz"), "b!/c.png"))
+        );
+    }
+
+    #[test]
+    fn member_path_round_trips() {
+        let path = member_path(Path::new("comics/issue1.cbz"), "pages/01.png");
+        assert_eq!(path, Path::new("comics/issue1.cbz!/pages/01.png"));
+        assert_eq!(
+            split_member(&path),
+            Some((Path::new("comics/issue1.cbz"), "pages/01.png"))
+        );
+        assert_eq!(on_disk_path(&path), Path::new("comics/issue1.cbz"));
+        assert_eq!(on_disk_path(Path::new("a.png")), Path::new("a.png"));
+    }
+}
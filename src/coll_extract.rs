@@ -0,0 +1,153 @@
+//! Extracting the entries matching a filter into a new collection, the opposite of merging
+
+use {
+    crate::{
+        archive,
+        collection::Collection,
+        db::UidCounter,
+        entry,
+        filter_reqs::Requirements,
+        import::{self, ImportMode},
+        tag::{self, Tag},
+    },
+    fnv::{FnvHashMap, FnvHashSet},
+    std::path::Path,
+};
+
+/// Create a new collection rooted at `new_root` from the entries of `coll` matching `reqs`.
+///
+/// The tags the entries use are carried along, along with the tags they imply,
+/// as well as the sequences and tag specific apps involved.
+///
+/// If `copy_files` is false, the entries have to be inside `new_root`, otherwise they are copied
+/// there, keeping their paths relative to `root`.
+///
+/// Returns the new collection, and the errors for entries that couldn't be carried along.
+pub fn extract(
+    coll: &Collection,
+    root: &Path,
+    reqs: &Requirements,
+    new_root: &Path,
+    copy_files: bool,
+    uid_counter: &mut UidCounter,
+) -> (Collection, Vec<String>) {
+    let mut new = Collection::make_new(uid_counter, &[] as &[&Path]);
+    new.ignored_extensions.clone_from(&coll.ignored_extensions);
+    new.ignore_patterns.clone_from(&coll.ignore_patterns);
+    new.link_policy = coll.link_policy;
+    new.archive_policy = coll.archive_policy;
+    let mut ids: Vec<entry::Id> = coll.filter(reqs).collect();
+    ids.sort_by_key(|id| &coll.entries[id].path);
+    let mut errors = Vec::new();
+    let mut entry_map: FnvHashMap<entry::Id, entry::Id> = FnvHashMap::default();
+    let mut used_tags = FnvHashSet::default();
+    // Whether archives were copied successfully, so they're only copied once for all members
+    let mut copied_archives: FnvHashMap<&Path, bool> = FnvHashMap::default();
+    for id in ids {
+        let en = &coll.entries[&id];
+        let abs_path = root.join(&en.path);
+        let path = if copy_files {
+            let on_disk = archive::on_disk_path(&en.path);
+            let copied = match copied_archives.get(on_disk) {
+                Some(&copied) => copied,
+                None => {
+                    let result = import::transfer(
+                        &root.join(on_disk),
+                        &new_root.join(on_disk),
+                        ImportMode::Copy,
+                    );
+                    if let Err(e) = &result {
+                        errors.push(format!("{}: {e}", on_disk.display()));
+                    }
+                    if on_disk != en.path {
+                        copied_archives.insert(on_disk, result.is_ok());
+                    }
+                    result.is_ok()
+                }
+            };
+            if !copied {
+                continue;
+            }
+            en.path.clone()
+        } else {
+            match abs_path.strip_prefix(new_root) {
+                Ok(rel) => rel.to_owned(),
+                Err(_) => {
+                    errors.push(format!("{}: not inside the new root", en.path.display()));
+                    continue;
+                }
+            }
+        };
+        let new_id = new.add_new_entry(path, uid_counter);
+        used_tags.extend(en.tags.iter().copied());
+        entry_map.insert(id, new_id);
+    }
+    // Implied tags are needed for the implications to keep working
+    let mut to_visit: Vec<tag::Id> = used_tags.iter().copied().collect();
+    while let Some(id) = to_visit.pop() {
+        if let Some(tag) = coll.tags.get(&id) {
+            for imp in &tag.implies {
+                if used_tags.insert(*imp) {
+                    to_visit.push(*imp);
+                }
+            }
+        }
+    }
+    let mut tag_ids: Vec<tag::Id> = used_tags.into_iter().collect();
+    tag_ids.sort_by_key(|id| id.0);
+    let mut tag_map: FnvHashMap<tag::Id, tag::Id> = FnvHashMap::default();
+    for id in &tag_ids {
+        if coll.tags.contains_key(id) {
+            tag_map.insert(*id, tag::Id(uid_counter.next()));
+        }
+    }
+    for (id, new_id) in &tag_map {
+        let tag = &coll.tags[id];
+        new.tags.insert(
+            *new_id,
+            Tag {
+                names: tag.names.clone(),
+                implies: tag
+                    .implies
+                    .iter()
+                    .filter_map(|imp| tag_map.get(imp).copied())
+                    .collect(),
+            },
+        );
+    }
+    for (id, new_id) in &entry_map {
+        if let Some(new_en) = new.entries.get_mut(new_id) {
+            new_en.tags = coll.entries[id]
+                .tags
+                .iter()
+                .filter_map(|tag| tag_map.get(tag).copied())
+                .collect();
+        }
+        if let Some(&hash) = coll.perceptual_hashes.get(id) {
+            new.perceptual_hashes.insert(*new_id, hash);
+        }
+    }
+    let mut seq_ids: Vec<_> = coll.sequences.keys().copied().collect();
+    seq_ids.sort_by_key(|id| id.0);
+    for seq_id in seq_ids {
+        let seq = &coll.sequences[&seq_id];
+        let ids: Vec<entry::Id> = seq
+            .entries
+            .iter()
+            .filter_map(|id| entry_map.get(id).copied())
+            .collect();
+        if ids.is_empty() {
+            continue;
+        }
+        let new_seq_id = new.add_new_sequence(&seq.name, uid_counter);
+        if let Some(new_seq) = new.sequences.get_mut(&new_seq_id) {
+            new_seq.entries = ids;
+        }
+    }
+    for (tag, app) in &coll.tag_specific_apps {
+        if let Some(new_tag) = tag_map.get(tag) {
+            new.tag_specific_apps.insert(*new_tag, *app);
+        }
+    }
+    (new, errors)
+}
@@ -0,0 +1,310 @@
+//! Merging one collection into another
+//!
+//! The target collection is the active one, so its root is the working directory.
+
+use {
+    crate::{
+        archive,
+        collection::Collection,
+        db::UidCounter,
+        entry,
+        import::{self, ImportMode},
+        move_rules::{is_inside_root, numbered_free_path},
+        tag::{self, Tag},
+    },
+    anyhow::bail,
+    fnv::{FnvHashMap, FnvHashSet},
+    std::path::{Path, PathBuf},
+};
+
+/// What to do with entries of the merged collection whose files are outside the target root
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutsideFiles {
+    #[default]
+    Copy,
+    Move,
+    /// Leave them out of the merge
+    Skip,
+}
+
+impl OutsideFiles {
+    pub const ALL: [Self; 3] = [Self::Copy, Self::Move, Self::Skip];
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Copy => "Copy into target",
+            Self::Move => "Move into target",
+            Self::Skip => "Leave out",
+        }
+    }
+}
+
+/// A tag of the merged collection whose names map to several tags of the target
+pub struct TagConflict {
+    pub tag: tag::Id,
+    pub candidates: Vec<tag::Id>,
+    /// Index into `candidates` of the tag to merge into
+    pub choice: usize,
+}
+
+/// How the merged collection maps onto the target, worked out before merging
+pub struct MergePlan {
+    /// Tags that match exactly one tag of the target by name
+    matched: FnvHashMap<tag::Id, tag::Id>,
+    pub conflicts: Vec<TagConflict>,
+    pub n_new_tags: usize,
+    /// Entries whose files are already inside the target root
+    pub n_inside: usize,
+    pub n_outside: usize,
+}
+
+pub fn plan(
+    target: &Collection,
+    root: &Path,
+    merged: &Collection,
+    merged_root: &Path,
+) -> MergePlan {
+    let names = name_map(target);
+    let mut plan = MergePlan {
+        matched: FnvHashMap::default(),
+        conflicts: Vec::new(),
+        n_new_tags: 0,
+        n_inside: 0,
+        n_outside: 0,
+    };
+    let mut tag_ids: Vec<tag::Id> = merged.tags.keys().copied().collect();
+    tag_ids.sort_by_key(|id| id.0);
+    for id in tag_ids {
+        let mut candidates = Vec::new();
+        for name in &merged.tags[&id].names {
+            if let Some(&cand) = names.get(name.as_str())
+                && !candidates.contains(&cand)
+            {
+                candidates.push(cand);
+            }
+        }
+        match candidates[..] {
+            [] => plan.n_new_tags += 1,
+            [cand] => {
+                plan.matched.insert(id, cand);
+            }
+            _ => plan.conflicts.push(TagConflict {
+                tag: id,
+                candidates,
+                choice: 0,
+            }),
+        }
+    }
+    for en in merged.entries.values() {
+        if merged_root.join(&en.path).starts_with(root) {
+            plan.n_inside += 1;
+        } else {
+            plan.n_outside += 1;
+        }
+    }
+    plan
+}
+
+/// What a merge did with the files of the merged collection
+#[derive(Default)]
+pub struct MergeOutcome {
+    /// Files that couldn't be copied or moved
+    pub errors: Vec<String>,
+    /// Paths of the entries whose files were moved into the target, relative to the merged root
+    pub moved: Vec<PathBuf>,
+    /// Number of entries outside the target root that were left out or failed to transfer
+    pub left_behind: usize,
+}
+
+/// Merge `merged` into `target`, giving everything new Uids.
+///
+/// Files outside the target root are handled according to `outside`, and end up
+/// in `subfolder`, keeping their structure.
+pub fn execute(
+    target: &mut Collection,
+    root: &Path,
+    merged: Collection,
+    merged_root: &Path,
+    plan: &MergePlan,
+    outside: OutsideFiles,
+    subfolder: &Path,
+    uid_counter: &mut UidCounter,
+) -> anyhow::Result<MergeOutcome> {
+    if !is_inside_root(subfolder) {
+        bail!("Destination folder must be inside the collection");
+    }
+    let mut names = name_map(target)
+        .into_keys()
+        .map(str::to_owned)
+        .collect::<FnvHashSet<_>>();
+    // Tags
+    let mut tag_map: FnvHashMap<tag::Id, tag::Id> = plan.matched.clone();
+    for conflict in &plan.conflicts {
+        tag_map.insert(conflict.tag, conflict.candidates[conflict.choice]);
+    }
+    let mut tag_ids: Vec<tag::Id> = merged.tags.keys().copied().collect();
+    tag_ids.sort_by_key(|id| id.0);
+    for id in &tag_ids {
+        let new_names: Vec<String> = merged.tags[id]
+            .names
+            .iter()
+            .filter(|name| names.insert((*name).clone()))
+            .cloned()
+            .collect();
+        match tag_map.get(id) {
+            Some(target_id) => {
+                if let Some(tag) = target.tags.get_mut(target_id) {
+                    tag.names.extend(new_names);
+                }
+            }
+            None => {
+                let new_id = tag::Id(uid_counter.next());
+                target.tags.insert(
+                    new_id,
+                    Tag {
+                        names: new_names,
+                        implies: Default::default(),
+                    },
+                );
+                tag_map.insert(*id, new_id);
+            }
+        }
+    }
+    for id in &tag_ids {
+        let Some(&target_id) = tag_map.get(id) else {
+            continue;
+        };
+        let implies: Vec<tag::Id> = merged.tags[id]
+            .implies
+            .iter()
+            .filter_map(|imp| tag_map.get(imp).copied())
+            .filter(|&imp| imp != target_id)
+            .collect();
+        if let Some(tag) = target.tags.get_mut(&target_id) {
+            tag.implies.extend(implies);
+        }
+    }
+    // Entries
+    let mut by_path: FnvHashMap<PathBuf, entry::Id> = target
+        .entries
+        .iter()
+        .map(|(id, en)| (en.path.clone(), *id))
+        .collect();
+    let mut entry_map: FnvHashMap<entry::Id, entry::Id> = FnvHashMap::default();
+    let mut outcome = MergeOutcome::default();
+    // Where each archive with members ended up, `None` if transferring it failed
+    let mut transferred_archives: FnvHashMap<PathBuf, Option<PathBuf>> = FnvHashMap::default();
+    let mut entries: Vec<_> = merged.entries.into_iter().collect();
+    entries.sort_by(|(_, a), (_, b)| a.path.cmp(&b.path));
+    for (id, en) in entries {
+        let abs_path = merged_root.join(&en.path);
+        let path = match abs_path.strip_prefix(root) {
+            Ok(rel) => rel.to_owned(),
+            Err(_) => {
+                let mode = match outside {
+                    OutsideFiles::Copy => ImportMode::Copy,
+                    OutsideFiles::Move => ImportMode::Move,
+                    OutsideFiles::Skip => {
+                        outcome.left_behind += 1;
+                        continue;
+                    }
+                };
+                // Archive members go along with their archive, which is transferred only once
+                let member = archive::split_member(&en.path);
+                let on_disk = archive::on_disk_path(&en.path);
+                let dest = match transferred_archives.get(on_disk) {
+                    Some(dest) => dest.clone(),
+                    None => {
+                        let src = merged_root.join(on_disk);
+                        let mut dest = subfolder.join(on_disk);
+                        let is_taken = |path: &Path| by_path.contains_key(path) || path.exists();
+                        if is_taken(&dest) {
+                            dest = numbered_free_path(&dest, is_taken);
+                        }
+                        let result = import::transfer(&src, &dest, mode);
+                        if let Err(e) = &result {
+                            outcome.errors.push(format!("{}: {e}", src.display()));
+                        }
+                        let dest = result.is_ok().then_some(dest);
+                        if member.is_some() {
+                            transferred_archives.insert(on_disk.to_owned(), dest.clone());
+                        }
+                        dest
+                    }
+                };
+                let Some(dest) = dest else {
+                    outcome.left_behind += 1;
+                    continue;
+                };
+                if mode == ImportMode::Move {
+                    outcome.moved.push(en.path.clone());
+                }
+                match member {
+                    Some((_, name)) => archive::member_path(&dest, name),
+                    None => dest,
+                }
+            }
+        };
+        let target_id = match by_path.get(&path) {
+            Some(&existing) => existing,
+            None => {
+                let new_id = target.add_new_entry(path.clone(), uid_counter);
+                by_path.insert(path, new_id);
+                new_id
+            }
+        };
+        if let Some(target_en) = target.entries.get_mut(&target_id) {
+            target_en
+                .tags
+                .extend(en.tags.iter().filter_map(|tag| tag_map.get(tag)));
+        }
+        if let Some(&hash) = merged.perceptual_hashes.get(&id) {
+            target.perceptual_hashes.entry(target_id).or_insert(hash);
+        }
+        entry_map.insert(id, target_id);
+    }
+    // Sequences
+    for seq in merged.sequences.into_values() {
+        let mut ids = Vec::new();
+        for id in seq.entries.iter().filter_map(|id| entry_map.get(id)) {
+            if !ids.contains(id) {
+                ids.push(*id);
+            }
+        }
+        if ids.is_empty() {
+            continue;
+        }
+        let seq_id = target.add_new_sequence(&seq.name, uid_counter);
+        if let Some(new_seq) = target.sequences.get_mut(&seq_id) {
+            new_seq.entries = ids;
+        }
+    }
+    // The target's own choice of app wins
+    for (tag, app) in merged.tag_specific_apps {
+        if let Some(&target_tag) = tag_map.get(&tag) {
+            target.tag_specific_apps.entry(target_tag).or_insert(app);
+        }
+    }
+    Ok(outcome)
+}
+
+/// Drop the entries whose files were moved out of the merged collection, so it doesn't keep
+/// entries for files that are gone
+pub fn forget_moved(merged: &mut Collection, moved: &[PathBuf]) {
+    let moved: FnvHashSet<&Path> = moved.iter().map(PathBuf::as_path).collect();
+    merged
+        .entries
+        .retain(|_, en| !moved.contains(en.path.as_path()));
+    for seq in merged.sequences.values_mut() {
+        seq.entries.retain(|id| merged.entries.contains_key(id));
+    }
+    merged
+        .perceptual_hashes
+        .retain(|id, _| merged.entries.contains_key(id));
+}
+
+fn name_map(coll: &Collection) -> FnvHashMap<&str, tag::Id> {
+    coll.tags
+        .iter()
+        .flat_map(|(id, tag)| tag.names.iter().map(|name| (name.as_str(), *id)))
+        .collect()
+}
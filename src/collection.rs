@@ -1,19 +1,25 @@
 use {
     crate::{
+        archive,
         db::{EntryMap, EntrySet, FolderChanges, Uid, UidCounter},
+        dlog,
         entry::{self, Entry},
         filter_reqs::Requirements,
-        folder_scan::walkdir,
+        folder_scan::{
+            self, file_id, walk_builder, ChangesScan, FileId, FileIdCache, IgnoreChecker,
+        },
+        folder_watch::TouchedPaths,
+        move_rules::{MoveBatch, MoveRule},
+        phash::{self, StoredHash},
         preferences,
         sequence::{self, Sequence},
         tag::{self, Tag},
     },
     anyhow::{bail, Context},
-    fnv::FnvHashMap,
+    fnv::{FnvHashMap, FnvHashSet},
     serde_derive::{Deserialize, Serialize},
     std::{
         borrow::Cow,
-        ffi::OsStr,
         path::{Path, PathBuf},
     },
     thiserror::Error,
@@ -23,6 +29,8 @@ pub type Entries = EntryMap<Entry>;
 pub type Tags = FnvHashMap<tag::Id, Tag>;
 pub type Sequences = FnvHashMap<sequence::Id, Sequence>;
 pub type TagSpecificApps = FnvHashMap<tag::Id, preferences::AppId>;
+/// Named filter queries, by name
+pub type SavedSearches = FnvHashMap<String, String>;
 
 pub trait TagsExt {
     fn first_name_of(&self, id: &tag::Id) -> Cow<str>;
@@ -52,6 +60,50 @@ pub struct Collection {
     /// Extensions that are ignored when updating from folder contents
     #[serde(default)]
     pub ignored_extensions: Vec<String>,
+    /// Gitignore-style patterns for paths to skip when updating from folder contents.
+    ///
+    /// These apply on top of any `.cowbumpignore` files in the folder.
+    #[serde(default)]
+    pub ignore_patterns: Vec<String>,
+    /// Saved filter queries that can be referred to with `@saved[name]`
+    #[serde(default)]
+    pub saved_searches: SavedSearches,
+    /// Apply changes found by the folder watcher without asking.
+    ///
+    /// Only meant for folders we trust not to get junk added to them.
+    #[serde(default)]
+    pub auto_apply_changes: bool,
+    #[serde(default)]
+    pub link_policy: LinkPolicy,
+    #[serde(default)]
+    pub archive_policy: ArchivePolicy,
+    /// Cached perceptual hashes of entries, see [`crate::phash`]
+    #[serde(default)]
+    pub perceptual_hashes: EntryMap<StoredHash>,
+    /// Rules for moving entries into folders, see [`crate::move_rules`]
+    #[serde(default)]
+    pub move_rules: Vec<MoveRule>,
+    /// Moves done by the rules, most recent last, so they can be undone
+    #[serde(default)]
+    pub move_journal: Vec<MoveBatch>,
+}
+
+/// How symlinks and hardlinks in the collection folder are treated
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct LinkPolicy {
+    /// Descend into symlinked folders. Symlink loops are detected and skipped.
+    pub follow_symlinks: bool,
+    /// Files reachable through several links (hardlinks, symlinks to files) get only one entry
+    pub dedup_inodes: bool,
+}
+
+/// How ZIP/CBZ archives in the collection folder are treated, see [`crate::archive`]
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct ArchivePolicy {
+    /// Add the images inside archives as entries, instead of the archives themselves
+    pub expand: bool,
+    /// Put the images of each newly added archive into a sequence named after the archive
+    pub make_sequences: bool,
 }
 
 #[derive(Hash, PartialEq, Eq, Serialize, Deserialize, Clone, Copy, Debug)]
@@ -65,6 +117,14 @@ impl Collection {
             sequences: Sequences::default(),
             tag_specific_apps: TagSpecificApps::default(),
             ignored_extensions: Vec::new(),
+            ignore_patterns: Vec::new(),
+            saved_searches: SavedSearches::default(),
+            auto_apply_changes: false,
+            link_policy: LinkPolicy::default(),
+            archive_policy: ArchivePolicy::default(),
+            perceptual_hashes: EntryMap::default(),
+            move_rules: Vec::new(),
+            move_journal: Vec::new(),
         };
         coll.update_from_paths(uid_counter, paths);
         coll
@@ -72,23 +132,27 @@ impl Collection {
     pub fn update_from_paths(&mut self, uid_counter: &mut UidCounter, paths: &[impl AsRef<Path>]) {
         // Indices in the entries vector that correspond to valid entries that exist
         let mut valid_uids = EntrySet::default();
-
-        for path in paths {
-            let path = path.as_ref();
-            let mut already_have = false;
-            for (&uid, en) in &self.entries {
-                if en.path == path {
-                    already_have = true;
-                    valid_uids.insert(uid);
-                    break;
+        let mut new_paths = Vec::new();
+        {
+            let uids_by_path: FnvHashMap<&Path, entry::Id> = self
+                .entries
+                .iter()
+                .map(|(&uid, en)| (en.path.as_path(), uid))
+                .collect();
+            for path in paths {
+                let path = path.as_ref();
+                match uids_by_path.get(path) {
+                    Some(&uid) => {
+                        valid_uids.insert(uid);
+                    }
+                    None => new_paths.push(path.to_owned()),
                 }
             }
-            let should_add = !already_have;
-            if should_add {
-                let uid = entry::Id(uid_counter.next());
-                valid_uids.insert(uid);
-                self.entries.insert(uid, Entry::new(path.to_owned()));
-            }
+        }
+        for path in new_paths {
+            let uid = entry::Id(uid_counter.next());
+            valid_uids.insert(uid);
+            self.entries.insert(uid, Entry::new(path));
         }
         // Remove indices that don't correspond to valid entries
         self.entries.retain(|uid, en| {
@@ -162,6 +226,23 @@ impl Collection {
         None
     }
 
+    /// Tag names similar to `word` by edit distance, closest first
+    pub(crate) fn similar_tag_names(&self, word: &str, max: usize) -> Vec<&str> {
+        // Allow roughly one typo per three characters
+        let max_dist = (word.chars().count() / 3).max(1);
+        let mut names: Vec<(usize, &str)> = self
+            .tags
+            .values()
+            .flat_map(|tag| &tag.names)
+            .filter_map(|name| {
+                let dist = edit_distance(word, name);
+                (dist <= max_dist).then_some((dist, name.as_str()))
+            })
+            .collect();
+        names.sort_unstable();
+        names.into_iter().take(max).map(|(_, name)| name).collect()
+    }
+
     pub fn remove_tags(&mut self, tags_to_del: &[tag::Id]) {
         self.tags.retain(|uid, _| {
             if tags_to_del.contains(uid) {
@@ -217,44 +298,219 @@ impl Collection {
             .and_then(|id| self.sequences.get(&id))
     }
 
-    pub(crate) fn scan_changes(&self, root: &Path) -> anyhow::Result<FolderChanges> {
-        let wd = walkdir(root);
-        let self_paths: Vec<_> = self.entries.values().map(|en| &en.path).collect();
+    /// Start scanning `root` for changes in the background
+    pub(crate) fn start_changes_scan(&self, id: Id, root: PathBuf) -> ChangesScan {
+        let known_paths = self.entries.values().map(|en| en.path.clone()).collect();
+        let hashed = self
+            .perceptual_hashes
+            .iter()
+            .filter_map(|(id, stored)| {
+                Some((*id, self.entries.get(id)?.path.clone(), stored.mtime))
+            })
+            .collect();
+        ChangesScan::start(
+            id,
+            root,
+            known_paths,
+            hashed,
+            self.ignored_extensions.clone(),
+            self.ignore_patterns.clone(),
+            self.link_policy,
+            self.archive_policy,
+        )
+    }
+
+    /// Work out the changes to the collection from a set of touched paths, relative to `root`
+    pub(crate) fn changes_for_paths(
+        &self,
+        root: &Path,
+        touched: &TouchedPaths,
+        file_ids: &mut FileIdCache,
+    ) -> anyhow::Result<FolderChanges> {
+        for path in &touched.paths {
+            file_ids.forget(path);
+        }
+        for (from, to) in &touched.renames {
+            file_ids.forget(from);
+            file_ids.forget(to);
+        }
+        let checker = IgnoreChecker::new(root, &self.ignore_patterns)?;
+        let known_paths: FnvHashSet<&Path> =
+            self.entries.values().map(|en| en.path.as_path()).collect();
+        let has_path = |path: &Path| known_paths.contains(path);
         let mut add = Vec::new();
         let mut remove = Vec::new();
-        // Scan for additions (paths we don't have)
-        for dir_entry in wd {
-            let dir_entry = dir_entry?;
-            if dir_entry.file_type().is_dir() {
-                continue;
+        let mut rename = Vec::new();
+        let mut paths = touched.paths.clone();
+        if !touched.renames.is_empty() {
+            // Original path and the path after the renames so far, so renames can chain
+            let mut moved: Vec<(&Path, PathBuf)> = self
+                .entries
+                .values()
+                .map(|en| (en.path.as_path(), en.path.clone()))
+                .collect();
+            for (from, to) in &touched.renames {
+                let is_dir = root.join(to).is_dir();
+                let ignored =
+                    checker.is_ignored(to, is_dir) || (!is_dir && self.has_ignored_ext(to));
+                let mut any = false;
+                for (_, now) in &mut moved {
+                    if let Some(new) = rebase(now, from, to) {
+                        *now = new;
+                        any = true;
+                    }
+                }
+                if !any && !ignored {
+                    // Not something we know about, but it could be new to us now
+                    paths.push(to.clone());
+                }
             }
-            let ignored_ext = dir_entry.path().extension().is_some_and(|ext| {
-                self.ignored_extensions
-                    .iter()
-                    .any(|ign_ext| ext == AsRef::<OsStr>::as_ref(ign_ext))
-            });
-            if ignored_ext {
-                continue;
+            for (orig, now) in moved {
+                if orig == now {
+                    continue;
+                }
+                let now_ignored = self.has_ignored_ext(archive::on_disk_path(&now))
+                    || checker.is_ignored(archive::on_disk_path(&now), false);
+                if now_ignored {
+                    remove.push(orig.to_owned());
+                } else {
+                    rename.push((orig.to_owned(), now));
+                }
             }
-            let dir_entry_path = dir_entry.into_path();
-            let dir_entry_path = match dir_entry_path.strip_prefix(root) {
-                Ok(stripped) => stripped,
-                Err(e) => {
-                    eprintln!("Failed to add entry {dir_entry_path:?}: {e}");
+        }
+        for path in &paths {
+            let abs_path = root.join(path);
+            if abs_path.is_dir() {
+                if checker.is_ignored(path, true)
+                    || (abs_path.is_symlink() && !self.link_policy.follow_symlinks)
+                {
                     continue;
                 }
-            };
-            if !self_paths.iter().any(|&p| p == dir_entry_path) {
-                add.push(dir_entry_path.to_owned());
+                // A folder was moved in, its contents don't get their own events
+                let walk =
+                    walk_builder(root, &abs_path, &self.ignore_patterns, self.link_policy)?.build();
+                for dir_entry in walk.filter_map(Result::ok) {
+                    if dir_entry.file_type().is_some_and(|ft| ft.is_dir()) {
+                        continue;
+                    }
+                    // The walk doesn't see ignore files above the moved in folder
+                    if let Ok(rel) = dir_entry.path().strip_prefix(root)
+                        && !self.has_ignored_ext(rel)
+                        && !checker.is_ignored(rel, false)
+                        && !has_path(rel)
+                    {
+                        add.push(rel.to_owned());
+                    }
+                }
+            } else if abs_path.exists() {
+                if self.has_ignored_ext(path) || checker.is_ignored(path, false) {
+                    continue;
+                }
+                if archive::is_expandable(path)
+                    && (self.archive_policy.expand || self.has_archive_members(path))
+                {
+                    // The archive could have been rewritten with different members
+                    let members = match archive::expand(root, path) {
+                        Ok(members) => members,
+                        Err(e) => {
+                            dlog!("Failed to read archive {path:?}: {e}");
+                            continue;
+                        }
+                    };
+                    remove.extend(
+                        self.entries
+                            .values()
+                            .filter(|en| {
+                                archive::on_disk_path(&en.path) == path
+                                    && !members.contains(&en.path)
+                            })
+                            .map(|en| en.path.clone()),
+                    );
+                    add.extend(members.into_iter().filter(|member| !has_path(member)));
+                } else if !has_path(path) {
+                    add.push(path.clone());
+                }
+            } else {
+                // Could be a file or a whole folder that went away
+                remove.extend(
+                    self.entries
+                        .values()
+                        .filter(|en| archive::on_disk_path(&en.path).starts_with(path))
+                        .map(|en| en.path.clone()),
+                );
             }
         }
-        // Scan for removes (paths we have but fs doesn't have)
-        for path in self_paths {
-            if !root.join(path).exists() {
-                remove.push(path.to_owned());
+        add.sort();
+        add.dedup();
+        if self.link_policy.dedup_inodes && !add.is_empty() {
+            let mut claimed: FnvHashSet<FileId> = self
+                .entries
+                .values()
+                .filter_map(|en| file_ids.get(root, &en.path))
+                .collect();
+            add.retain(|path| file_id(&root.join(path)).is_none_or(|id| claimed.insert(id)));
+        }
+        remove.sort();
+        remove.dedup();
+        rename.sort();
+        Ok(FolderChanges {
+            add,
+            remove,
+            rename,
+            stale_phashes: Vec::new(),
+        })
+    }
+
+    /// Cache perceptual hashes, skipping entries that aren't (or are no longer) in the collection
+    pub(crate) fn store_phashes(&mut self, hashes: EntryMap<StoredHash>) {
+        for (id, hash) in hashes {
+            if self.entries.contains_key(&id) {
+                self.perceptual_hashes.insert(id, hash);
             }
         }
-        Ok(FolderChanges { add, remove })
+    }
+
+    /// Perceptual hash of the image at `path`, relative to the collection root.
+    ///
+    /// Uses the cached hash if `path` is an entry, otherwise hashes the file.
+    pub(crate) fn phash_of_path(&self, path: &Path) -> image::ImageResult<u64> {
+        let cached = self
+            .entries
+            .iter()
+            .find(|(_, en)| en.path == path)
+            .and_then(|(id, _)| self.perceptual_hashes.get(id));
+        match cached {
+            Some(stored) => Ok(stored.hash),
+            None => phash::hash_file(path),
+        }
+    }
+
+    /// Hashed entries at most `max_dist` bits away from `hash`, closest first
+    pub(crate) fn similar_entries(&self, hash: u64, max_dist: u32) -> Vec<(entry::Id, u32)> {
+        let mut similar: Vec<(entry::Id, u32)> = self
+            .entries
+            .keys()
+            .filter_map(|id| {
+                let dist = phash::distance(hash, self.perceptual_hashes.get(id)?.hash);
+                (dist <= max_dist).then_some((*id, dist))
+            })
+            .collect();
+        similar.sort_by_key(|&(id, dist)| (dist, &self.entries[&id].path));
+        similar
+    }
+
+    /// Whether there are entries for images inside the archive at `archive_path`.
+    ///
+    /// These archives stay expanded even if expanding gets turned off.
+    fn has_archive_members(&self, archive_path: &Path) -> bool {
+        self.entries.values().any(|en| {
+            archive::split_member(&en.path).is_some_and(|(archive, _)| archive == archive_path)
+        })
+    }
+
+    /// Whether `path` has one of the ignored extensions
+    fn has_ignored_ext(&self, path: &Path) -> bool {
+        folder_scan::has_ignored_ext(path, &self.ignored_extensions)
     }
 
     pub(crate) fn apply_changes(
@@ -263,15 +519,69 @@ impl Collection {
         uid_counter: &mut UidCounter,
         mut callback: impl FnMut(&Path, entry::Id),
     ) {
+        let renames: FnvHashMap<&Path, &Path> = changes
+            .rename
+            .iter()
+            .map(|(from, to)| (from.as_path(), to.as_path()))
+            .collect();
+        for en in self.entries.values_mut() {
+            if let Some(to) = renames.get(en.path.as_path()) {
+                en.path = to.to_path_buf();
+            }
+        }
+        let mut added_members: FnvHashMap<&Path, Vec<entry::Id>> = FnvHashMap::default();
         for path in &changes.add {
             let id = self.add_new_entry(path.clone(), uid_counter);
+            if let Some((archive_path, _)) = archive::split_member(path) {
+                added_members.entry(archive_path).or_default().push(id);
+            }
             callback(path, id);
         }
+        if self.archive_policy.make_sequences {
+            for (archive_path, ids) in added_members {
+                self.add_archive_sequence(archive_path, &ids, uid_counter);
+            }
+        }
         self.entries
             .retain(|_k, en| !changes.remove.contains(&en.path));
+        self.perceptual_hashes
+            .retain(|id, _| self.entries.contains_key(id));
     }
 
-    fn add_new_entry(&mut self, path: PathBuf, uid_counter: &mut UidCounter) -> entry::Id {
+    /// Add the new members `ids` of the archive at `archive_path` to the sequence of the archive.
+    ///
+    /// That's the sequence that already has members of it, or a new one named after it.
+    fn add_archive_sequence(
+        &mut self,
+        archive_path: &Path,
+        ids: &[entry::Id],
+        uid_counter: &mut UidCounter,
+    ) {
+        let existing = self.sequences.iter().find_map(|(seq_id, seq)| {
+            seq.entries
+                .iter()
+                .any(|id| {
+                    self.entries
+                        .get(id)
+                        .is_some_and(|en| archive::on_disk_path(&en.path) == archive_path)
+                        && !ids.contains(id)
+                })
+                .then_some(*seq_id)
+        });
+        let seq_id = existing.unwrap_or_else(|| {
+            let name = archive_path.file_stem().map_or_else(
+                || archive_path.to_string_lossy(),
+                |stem| stem.to_string_lossy(),
+            );
+            self.add_new_sequence(&name, uid_counter)
+        });
+        self.add_entries_to_sequence(seq_id, ids);
+    }
+    pub(crate) fn add_new_entry(
+        &mut self,
+        path: PathBuf,
+        uid_counter: &mut UidCounter,
+    ) -> entry::Id {
         let uid = entry::Id(uid_counter.next());
         self.entries.insert(uid, Entry::new(path));
         uid
@@ -299,6 +609,65 @@ impl Collection {
         self.tags.remove(&merge);
         Ok(())
     }
+    /// Merge duplicate entries `others` into `keep`, removing them from the collection.
+    ///
+    /// `keep` gets all their tags, and takes their place in sequences it's not already part of.
+    pub(crate) fn merge_duplicates(
+        &mut self,
+        keep: entry::Id,
+        others: &[entry::Id],
+    ) -> anyhow::Result<()> {
+        if !self.entries.contains_key(&keep) {
+            bail!("Dangling entry id for kept entry: {keep:?}");
+        }
+        for seq in self.sequences.values_mut() {
+            let mut has_keep = seq.contains_entry(keep);
+            for &other in others {
+                let Some(pos) = seq.entries.iter().position(|&id| id == other) else {
+                    continue;
+                };
+                if has_keep {
+                    seq.entries.remove(pos);
+                } else {
+                    seq.entries[pos] = keep;
+                    has_keep = true;
+                }
+            }
+        }
+        let mut tags = Vec::new();
+        for id in others {
+            if let Some(en) = self.entries.remove(id) {
+                tags.extend(en.tags);
+            }
+            self.perceptual_hashes.remove(id);
+        }
+        if let Some(en) = self.entries.get_mut(&keep) {
+            en.tags.extend(tags);
+        }
+        Ok(())
+    }
+    /// Rename a saved search, also updating `@saved[old]` references in other saved searches.
+    ///
+    /// Names are lowercase, like tag names.
+    pub(crate) fn rename_saved_search(&mut self, old: &str, new: &str) -> anyhow::Result<()> {
+        let new = &new.to_ascii_lowercase();
+        if new.is_empty() {
+            bail!("Name can't be empty");
+        }
+        if self.saved_searches.contains_key(new) {
+            bail!("A saved search named {new:?} already exists");
+        }
+        let query = self
+            .saved_searches
+            .remove(old)
+            .context("No such saved search")?;
+        self.saved_searches.insert(new.to_owned(), query);
+        let (old_ref, new_ref) = (format!("@saved[{old}]"), format!("@saved[{new}]"));
+        for query in self.saved_searches.values_mut() {
+            *query = query.replace(&old_ref, &new_ref);
+        }
+        Ok(())
+    }
     fn replace_tag_refs(&mut self, replace: tag::Id, with: tag::Id) {
         // Entries
         for en in self.entries.values_mut() {
@@ -325,8 +694,27 @@ fn cleanse_tag_from_entries(entries: &mut Entries, tag_to_cleanse: tag::Id) {
     }
 }
 
+/// Where `path` ends up when `from` (a file or a folder) is renamed to `to`.
+///
+/// `None` if the rename doesn't affect `path`. Archive members follow their archive.
+fn rebase(path: &Path, from: &Path, to: &Path) -> Option<PathBuf> {
+    let rest = archive::on_disk_path(path).strip_prefix(from).ok()?;
+    let new = if rest.as_os_str().is_empty() {
+        to.to_owned()
+    } else {
+        to.join(rest)
+    };
+    Some(match archive::split_member(path) {
+        Some((_, member)) => archive::member_path(&new, member),
+        None => new,
+    })
+}
+
 /// Rename the last component (filename) of a `PathBuf`, and rename it on the filesystem too.
 fn pathbuf_rename_filename(buf: &mut PathBuf, new_name: &str) -> anyhow::Result<()> {
+    if archive::split_member(buf).is_some() {
+        bail!("Images inside archives can't be renamed");
+    }
     let mut new_buf = buf.clone();
     new_buf.pop();
     new_buf.push(new_name);
@@ -337,3 +725,19 @@ fn pathbuf_rename_filename(buf: &mut PathBuf, new_name: &str) -> anyhow::Result<
     *buf = new_buf;
     Ok(())
 }
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, ca) in a.chars().enumerate() {
+        cur[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let subst = prev[j] + usize::from(ca != cb);
+            cur[j + 1] = subst.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
@@ -0,0 +1,150 @@
+//! Renumbering all Uids densely, starting from 0
+//!
+//! Uids are never reused, so deleting things leaves holes in the Uid space.
+//! See `design/considerations.md`.
+
+use {
+    crate::{
+        collection::{self, Collection},
+        db::{CollMap, Db, EntryMap, Uid, UidCounter},
+        entry, filter_reqs,
+        move_rules::Move,
+        preferences::AppId,
+        sequence, tag,
+    },
+    fnv::FnvHashMap,
+    std::{hash::Hash, mem},
+};
+
+/// Old Uids of the items of a collection, mapped to their new Uids
+#[derive(Default)]
+pub struct CollUidMap {
+    pub entries: EntryMap<entry::Id>,
+    pub tags: FnvHashMap<tag::Id, tag::Id>,
+    pub sequences: FnvHashMap<sequence::Id, sequence::Id>,
+}
+
+/// Renumber everything in `db`, along with `colls`, which are all of its collections.
+///
+/// The ids in `colls` are updated to the new collection ids.
+/// References to things that don't exist are dropped.
+///
+/// Returns the Uid mappings of the items of each collection, by new collection id.
+pub fn compact(db: &mut Db, colls: &mut [(collection::Id, Collection)]) -> CollMap<CollUidMap> {
+    let mut counter = UidCounter::default();
+    colls.sort_by_key(|(id, _)| id.0);
+    let mut coll_map: CollMap<collection::Id> = CollMap::default();
+    for (id, _) in colls.iter_mut() {
+        let new_id = collection::Id(counter.next());
+        coll_map.insert(*id, new_id);
+        *id = new_id;
+    }
+    db.collections = mem::take(&mut db.collections)
+        .into_iter()
+        .filter_map(|(id, root)| Some((*coll_map.get(&id)?, root)))
+        .collect();
+    // `iter` goes from most to least recent
+    let recent: Vec<collection::Id> = db.recent.iter().copied().collect();
+    db.recent = Default::default();
+    for id in recent.iter().rev() {
+        if let Some(new_id) = coll_map.get(id) {
+            db.recent.use_(*new_id);
+        }
+    }
+    let apps = &mut db.preferences.applications;
+    let app_map = renumber_keys(apps, &mut counter, |id| id.0, AppId);
+    *apps = remap_keys(mem::take(apps), &app_map);
+    for app in db.preferences.associations.values_mut() {
+        *app = app.and_then(|id| app_map.get(&id).copied());
+    }
+    let mut maps = CollMap::default();
+    for (id, coll) in colls.iter_mut() {
+        maps.insert(*id, compact_coll(coll, &app_map, &mut counter));
+    }
+    db.uid_counter = counter;
+    maps
+}
+
+fn compact_coll(
+    coll: &mut Collection,
+    app_map: &FnvHashMap<AppId, AppId>,
+    counter: &mut UidCounter,
+) -> CollUidMap {
+    let map = CollUidMap {
+        tags: renumber_keys(&coll.tags, counter, |id| id.0, tag::Id),
+        entries: renumber_keys(&coll.entries, counter, |id| id.0, entry::Id),
+        sequences: renumber_keys(&coll.sequences, counter, |id| id.0, sequence::Id),
+    };
+    coll.tags = remap_keys(mem::take(&mut coll.tags), &map.tags);
+    for tag in coll.tags.values_mut() {
+        tag.implies = tag
+            .implies
+            .iter()
+            .filter_map(|id| map.tags.get(id).copied())
+            .collect();
+    }
+    coll.entries = remap_keys(mem::take(&mut coll.entries), &map.entries);
+    for en in coll.entries.values_mut() {
+        en.tags = en
+            .tags
+            .iter()
+            .filter_map(|id| map.tags.get(id).copied())
+            .collect();
+    }
+    coll.sequences = remap_keys(mem::take(&mut coll.sequences), &map.sequences);
+    for seq in coll.sequences.values_mut() {
+        seq.entries = seq
+            .entries
+            .iter()
+            .filter_map(|id| map.entries.get(id).copied())
+            .collect();
+    }
+    coll.tag_specific_apps = mem::take(&mut coll.tag_specific_apps)
+        .into_iter()
+        .filter_map(|(tag, app)| Some((*map.tags.get(&tag)?, *app_map.get(&app)?)))
+        .collect();
+    coll.perceptual_hashes = remap_keys(mem::take(&mut coll.perceptual_hashes), &map.entries);
+    for batch in &mut coll.move_journal {
+        batch.moves = mem::take(&mut batch.moves)
+            .into_iter()
+            .filter_map(|mv| {
+                Some(Move {
+                    id: *map.entries.get(&mv.id)?,
+                    ..mv
+                })
+            })
+            .collect();
+    }
+    coll.move_journal.retain(|batch| !batch.moves.is_empty());
+    // Queries can refer to entries by uid
+    for query in coll.saved_searches.values_mut() {
+        *query = filter_reqs::remap_similar_ids(query, &map.entries);
+    }
+    for rule in &mut coll.move_rules {
+        rule.query = filter_reqs::remap_similar_ids(&rule.query, &map.entries);
+    }
+    map
+}
+
+/// Give the keys of `map` new Uids, in the order of their old Uids
+fn renumber_keys<K: Copy + Eq + Hash, V>(
+    map: &FnvHashMap<K, V>,
+    counter: &mut UidCounter,
+    uid_of: impl Fn(&K) -> Uid,
+    make_id: impl Fn(Uid) -> K,
+) -> FnvHashMap<K, K> {
+    let mut keys: Vec<K> = map.keys().copied().collect();
+    keys.sort_unstable_by_key(uid_of);
+    keys.into_iter()
+        .map(|key| (key, make_id(counter.next())))
+        .collect()
+}
+
+fn remap_keys<K: Copy + Eq + Hash, V>(
+    map: FnvHashMap<K, V>,
+    key_map: &FnvHashMap<K, K>,
+) -> FnvHashMap<K, V> {
+    map.into_iter()
+        .filter_map(|(key, val)| Some((*key_map.get(&key)?, val)))
+        .collect()
+}
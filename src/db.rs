@@ -42,6 +42,14 @@ impl UidCounter {
         self.0 += 1;
         uid
     }
+    /// The Uid that will be handed out next
+    pub fn peek(&self) -> Uid {
+        self.0
+    }
+    /// Make sure `uid` is never handed out again
+    pub fn bump_past(&mut self, uid: Uid) {
+        self.0 = self.0.max(uid.saturating_add(1));
+    }
 }
 
 impl Db {
@@ -67,7 +75,11 @@ impl Db {
         key
     }
     pub fn save(&self) -> anyhow::Result<()> {
-        serialization::write_to_file(self, self.data_dir.join(FILENAME))
+        serialization::write_to_file(self, self.path())
+    }
+    /// Where the database is saved
+    pub fn path(&self) -> PathBuf {
+        self.data_dir.join(FILENAME)
     }
     /// Save backups of everything cowbump keeps track of.
     ///
@@ -114,11 +126,17 @@ impl Db {
 pub(crate) struct FolderChanges {
     pub(crate) add: Vec<PathBuf>,
     pub(crate) remove: Vec<PathBuf>,
+    /// Entry paths to change, old path first. Applied before adding and removing.
+    pub(crate) rename: Vec<(PathBuf, PathBuf)>,
+    /// Entries whose file was written to since their perceptual hash was cached.
+    ///
+    /// Not shown to the user, these hashes are just dropped.
+    pub(crate) stale_phashes: Vec<entry::Id>,
 }
 
 const FILENAME: &str = "cowbump.db";
 impl FolderChanges {
     pub(crate) fn empty(&self) -> bool {
-        self.add.is_empty() && self.remove.is_empty()
+        self.add.is_empty() && self.remove.is_empty() && self.rename.is_empty()
     }
 }
@@ -0,0 +1,106 @@
+//! Finding entries whose files have the exact same contents
+
+use {
+    crate::{collection, dlog, entry},
+    fnv::FnvHashMap,
+    std::{
+        fs::File,
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        thread::JoinHandle,
+    },
+};
+
+/// Groups of entries with identical file contents
+pub type DupGroups = Vec<Vec<entry::Id>>;
+
+/// Progress of a duplicate search, shared with the hashing thread
+#[derive(Default)]
+pub struct DupProgress {
+    pub hashed: AtomicUsize,
+    /// Only files that share their size with another file need hashing
+    pub to_hash: AtomicUsize,
+}
+
+/// Group the files by content. Files that can't be read are left out.
+///
+/// Sizes are compared first, so most files never have to be read.
+pub fn find_duplicates(files: &[(entry::Id, PathBuf)], progress: &DupProgress) -> DupGroups {
+    let mut by_size: FnvHashMap<u64, Vec<&(entry::Id, PathBuf)>> = FnvHashMap::default();
+    for file in files {
+        match std::fs::metadata(&file.1) {
+            Ok(meta) => by_size.entry(meta.len()).or_default().push(file),
+            Err(e) => dlog!("Duplicate search: can't stat {:?}: {e}", file.1),
+        }
+    }
+    by_size.retain(|_, files| files.len() > 1);
+    progress
+        .to_hash
+        .store(by_size.values().map(Vec::len).sum(), Ordering::Relaxed);
+    let mut by_hash: FnvHashMap<(u64, blake3::Hash), Vec<&(entry::Id, PathBuf)>> =
+        FnvHashMap::default();
+    for (size, files) in by_size {
+        for file in files {
+            match hash_file(&file.1) {
+                Ok(hash) => by_hash.entry((size, hash)).or_default().push(file),
+                Err(e) => dlog!("Duplicate search: can't read {:?}: {e}", file.1),
+            }
+            progress.hashed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    let mut groups: Vec<Vec<&(entry::Id, PathBuf)>> = by_hash
+        .into_values()
+        .filter(|files| files.len() > 1)
+        .collect();
+    for group in &mut groups {
+        group.sort_by(|a, b| a.1.cmp(&b.1));
+    }
+    groups.sort_by(|a, b| a[0].1.cmp(&b[0].1));
+    groups
+        .into_iter()
+        .map(|group| group.into_iter().map(|(id, _)| *id).collect())
+        .collect()
+}
+
+fn hash_file(path: &std::path::Path) -> std::io::Result<blake3::Hash> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(File::open(path)?)?;
+    Ok(hasher.finalize())
+}
+
+/// A duplicate search running in the background
+pub struct DupScan {
+    /// The collection being searched
+    pub coll_id: collection::Id,
+    pub progress: Arc<DupProgress>,
+    handle: Option<JoinHandle<DupGroups>>,
+}
+
+impl DupScan {
+    /// Start searching `files`. The paths should be absolute, the working directory can change.
+    pub fn start(coll_id: collection::Id, files: Vec<(entry::Id, PathBuf)>) -> Self {
+        let progress = Arc::new(DupProgress::default());
+        let progress_clone = Arc::clone(&progress);
+        let handle = std::thread::spawn(move || find_duplicates(&files, &progress_clone));
+        Self {
+            coll_id,
+            progress,
+            handle: Some(handle),
+        }
+    }
+    /// Returns the result once the search is done
+    pub fn poll(&mut self) -> Option<anyhow::Result<DupGroups>> {
+        if !self.handle.as_ref()?.is_finished() {
+            return None;
+        }
+        let handle = self.handle.take()?;
+        Some(
+            handle
+                .join()
+                .map_err(|_| anyhow::anyhow!("Duplicate search thread panicked")),
+        )
+    }
+}
@@ -47,7 +47,10 @@ impl Entry {
             Req::Not(req) => !self.req_satisfied(id, req, tags, sequences),
             Req::FilenameSub(fsub) => self.path.to_string_lossy().to_lowercase().contains(fsub),
             Req::PartOfSeq => sequences.values().any(|seq| seq.contains_entry(id)),
-            Req::NTags(n) => self.tags.len() == *n,
+            Req::NTags(cmp) => cmp.matches(self.tags.len()),
+            Req::Implied(id) => !self.tags.contains(id) && self.satisfies_required_tag(*id, tags),
+            Req::Saved { reqs, .. } => reqs.all(|req| self.req_satisfied(id, req, tags, sequences)),
+            Req::Similar { matches, .. } => matches.contains(&id),
         }
     }
     fn satisfies_required_tag(&self, required_tag_id: tag::Id, tags: &Tags) -> bool {
@@ -55,6 +58,18 @@ impl Entry {
             .iter()
             .any(|tag_id| tag_satisfies_required_tag(*tag_id, required_tag_id, tags, &mut 0))
     }
+    /// Find the implication path from one of this entry's tags to `required_tag_id`.
+    ///
+    /// The path begins with a tag the entry has explicitly, and ends with the required tag.
+    pub fn implication_path(&self, required_tag_id: tag::Id, tags: &Tags) -> Option<Vec<tag::Id>> {
+        if self.tags.contains(&required_tag_id) {
+            return Some(vec![required_tag_id]);
+        }
+        self.tags.iter().find_map(|&tag_id| {
+            let mut path = Vec::new();
+            find_implication_path(tag_id, required_tag_id, tags, &mut path).then_some(path)
+        })
+    }
     /// If `replace` is found, remove it, and insert `with`
     pub(crate) fn replace_tag(&mut self, replace: tag::Id, with: tag::Id) {
         if self.tags.remove(&replace) {
@@ -91,6 +106,32 @@ fn tag_satisfies_required_tag(
     })
 }
 
+/// Like [`tag_satisfies_required_tag`], but records the tags visited on the way
+fn find_implication_path(
+    tag_id: tag::Id,
+    required_tag_id: tag::Id,
+    tags: &Tags,
+    path: &mut Vec<tag::Id>,
+) -> bool {
+    // Same depth limit as `tag_satisfies_required_tag`
+    if path.len() == 10 {
+        return false;
+    }
+    path.push(tag_id);
+    if tag_id == required_tag_id {
+        return true;
+    }
+    if let Some(tag) = tags.get(&tag_id)
+        && tag.implies.iter().any(|implied_tag_id| {
+            find_implication_path(*implied_tag_id, required_tag_id, tags, path)
+        })
+    {
+        return true;
+    }
+    path.pop();
+    false
+}
+
 pub fn filter_map(
     uid: Id,
     entry: &Entry,
@@ -0,0 +1,109 @@
+//! Reading EXIF metadata of images, and applying the orientation it specifies
+//!
+//! Phone cameras usually store pixels in sensor order, and only record in EXIF
+//! how the image should be rotated for display.
+
+use {
+    exif::{Exif, In, Reader, Tag},
+    image::{DynamicImage, ImageResult},
+    std::io::Cursor,
+};
+
+/// Decode an image, rotating and flipping it upright according to its EXIF orientation
+pub fn load_oriented(data: &[u8]) -> ImageResult<DynamicImage> {
+    let img = image::load_from_memory(data)?;
+    Ok(match read(data) {
+        Some(exif) => apply_orientation(img, orientation(&exif)),
+        None => img,
+    })
+}
+
+/// The EXIF fields of the main image in `data`, as (tag, value) display strings.
+///
+/// Empty if there is no EXIF data.
+pub fn fields(data: &[u8]) -> Vec<(String, String)> {
+    let Some(exif) = read(data) else {
+        return Vec::new();
+    };
+    exif.fields()
+        .filter(|field| field.ifd_num == In::PRIMARY)
+        .map(|field| {
+            (
+                field.tag.to_string(),
+                field.display_value().with_unit(&exif).to_string(),
+            )
+        })
+        .collect()
+}
+
+fn read(data: &[u8]) -> Option<Exif> {
+    Reader::new()
+        .read_from_container(&mut Cursor::new(data))
+        .ok()
+}
+
+/// The EXIF orientation value, 1 (upright) if it's missing
+fn orientation(exif: &Exif) -> u32 {
+    exif.get_field(Tag::Orientation, In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+        .unwrap_or(1)
+}
+
+/// Transform `img` according to EXIF orientation value `orientation`
+fn apply_orientation(img: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        // Transpose
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        // Transverse
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::apply_orientation,
+        image::{DynamicImage, GrayImage, Luma},
+    };
+
+    /// Rows of pixel values of `img` after applying `orientation` to it.
+    ///
+    /// `img` is 2x3, with the value of each pixel being `x + 10 * y`.
+    fn oriented_rows(orientation: u32) -> Vec<Vec<u8>> {
+        let img = GrayImage::from_fn(2, 3, |x, y| Luma([(x + 10 * y) as u8]));
+        let img = apply_orientation(DynamicImage::ImageLuma8(img), orientation).into_luma8();
+        img.rows()
+            .map(|row| row.map(|px| px.0[0]).collect())
+            .collect()
+    }
+
+    #[test]
+    fn orientations() {
+        let cases: [(u32, &[&[u8]]); 8] = [
+            (1, &[&[0, 1], &[10, 11], &[20, 21]]),
+            (2, &[&[1, 0], &[11, 10], &[21, 20]]),
+            (3, &[&[21, 20], &[11, 10], &[1, 0]]),
+            (4, &[&[20, 21], &[10, 11], &[0, 1]]),
+            (5, &[&[0, 10, 20], &[1, 11, 21]]),
+            (6, &[&[20, 10, 0], &[21, 11, 1]]),
+            (7, &[&[21, 11, 1], &[20, 10, 0]]),
+            (8, &[&[1, 11, 21], &[0, 10, 20]]),
+        ];
+        for (orientation, expected) in cases {
+            assert_eq!(oriented_rows(orientation), expected, "{orientation}");
+        }
+    }
+
+    #[test]
+    fn invalid_orientation_leaves_image_alone() {
+        for orientation in [0, 9, u32::MAX] {
+            assert_eq!(oriented_rows(orientation), oriented_rows(1));
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Telling what kind of file an entry is, by its magic bytes and extension
+
+use {
+    crate::archive,
+    std::{
+        fs::File,
+        io::{self, Read},
+        path::Path,
+    },
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FileType {
+    /// Something the `image` crate can (hopefully) decode
+    Image,
+    Pdf,
+    Video,
+    Audio,
+    Text,
+    Archive,
+    Other,
+}
+
+/// How many bytes from the start of the file are looked at
+const HEADER_LEN: usize = 64;
+
+/// Detect the type of the file at `path`, reading only the start of it.
+///
+/// Magic bytes take precedence over the extension, since extensions lie more often.
+/// Archive members only go by their extension, so they don't have to be extracted.
+pub fn detect(path: &Path) -> io::Result<FileType> {
+    if archive::split_member(path).is_some() {
+        return Ok(by_extension(path).unwrap_or(FileType::Other));
+    }
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    File::open(path)?
+        .take(HEADER_LEN as u64)
+        .read_to_end(&mut header)?;
+    Ok(by_magic(&header, path)
+        .or_else(|| by_extension(path))
+        .unwrap_or_else(|| sniff_text(&header)))
+}
+
+fn by_magic(header: &[u8], path: &Path) -> Option<FileType> {
+    let starts = |magic: &[u8]| header.starts_with(magic);
+    let at =
+        |offset: usize, magic: &[u8]| header.get(offset..).is_some_and(|h| h.starts_with(magic));
+    if starts(b"%PDF") {
+        return Some(FileType::Pdf);
+    }
+    // ISO base media: mp4, mov, but also avif and heic
+    if at(4, b"ftyp") {
+        let is_image = [b"avif", b"avis", b"heic", b"heix", b"mif1", b"msf1"]
+            .iter()
+            .any(|brand| at(8, *brand));
+        return Some(if is_image {
+            FileType::Image
+        } else if at(8, b"M4A ") {
+            FileType::Audio
+        } else {
+            FileType::Video
+        });
+    }
+    if starts(b"RIFF") {
+        if at(8, b"AVI ") {
+            return Some(FileType::Video);
+        }
+        if at(8, b"WAVE") {
+            return Some(FileType::Audio);
+        }
+    }
+    // Matroska and WebM
+    if starts(&[0x1A, 0x45, 0xDF, 0xA3]) || starts(b"FLV") {
+        return Some(FileType::Video);
+    }
+    if starts(b"OggS") {
+        // Ogg can be either, the extension knows better
+        return Some(by_extension(path).unwrap_or(FileType::Audio));
+    }
+    if starts(b"ID3") || starts(b"fLaC") || starts(&[0xFF, 0xFB]) || starts(&[0xFF, 0xF3]) {
+        return Some(FileType::Audio);
+    }
+    let archive_magics: [&[u8]; 6] = [
+        b"PK\x03\x04",
+        b"Rar!\x1A\x07",
+        b"7z\xBC\xAF\x27\x1C",
+        &[0x1F, 0x8B],
+        b"\xFD7zXZ\0",
+        b"BZh",
+    ];
+    if archive_magics.iter().any(|magic| starts(magic)) {
+        return Some(FileType::Archive);
+    }
+    if image::guess_format(header).is_ok() {
+        return Some(FileType::Image);
+    }
+    None
+}
+
+/// Guess the type from the extension alone, without touching the file
+pub fn by_extension(path: &Path) -> Option<FileType> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    let ty = match ext.as_str() {
+        "png" | "jpg" | "jpeg" | "gif" | "webp" | "bmp" | "tif" | "tiff" | "tga" | "ico"
+        | "pnm" | "pbm" | "pgm" | "ppm" | "qoi" | "exr" | "hdr" | "avif" | "dds" | "ff" => {
+            FileType::Image
+        }
+        "pdf" => FileType::Pdf,
+        "mp4" | "m4v" | "mkv" | "webm" | "avi" | "mov" | "wmv" | "flv" | "mpg" | "mpeg" | "ogv"
+        | "ts" | "3gp" => FileType::Video,
+        "mp3" | "flac" | "ogg" | "oga" | "opus" | "wav" | "m4a" | "aac" | "wma" | "mid"
+        | "midi" => FileType::Audio,
+        "txt" | "md" | "json" | "toml" | "yaml" | "yml" | "xml" | "html" | "htm" | "csv"
+        | "log" | "ini" | "rs" | "py" | "c" | "h" | "cpp" | "js" | "css" | "sh" => FileType::Text,
+        "zip" | "cbz" | "rar" | "cbr" | "7z" | "cb7" | "tar" | "gz" | "tgz" | "xz" | "bz2"
+        | "zst" => FileType::Archive,
+        _ => return None,
+    };
+    Some(ty)
+}
+
+/// Files without a known magic or extension are text if they start with printable UTF-8
+fn sniff_text(header: &[u8]) -> FileType {
+    // The header could end in the middle of a multibyte character
+    let valid = match std::str::from_utf8(header) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => {
+            std::str::from_utf8(&header[..e.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return FileType::Other,
+    };
+    if !header.is_empty()
+        && valid
+            .chars()
+            .all(|c| !c.is_control() || c.is_ascii_whitespace())
+    {
+        FileType::Text
+    } else {
+        FileType::Other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{by_magic, sniff_text, FileType},
+        std::path::Path,
+    };
+
+    fn magic(header: &[u8]) -> Option<FileType> {
+        by_magic(header, Path::new("file"))
+    }
+
+    #[test]
+    fn magic_bytes() {
+        assert_eq!(magic(b"%PDF-1.7\n"), Some(FileType::Pdf));
+        assert_eq!(magic(b"\0\0\0\x20ftypisom"), Some(FileType::Video));
+        assert_eq!(magic(b"\0\0\0\x20ftypavif"), Some(FileType::Image));
+        assert_eq!(magic(b"\0\0\0\x20ftypM4A "), Some(FileType::Audio));
+        assert_eq!(magic(b"RIFF\0\0\0\0AVI LIST"), Some(FileType::Video));
+        assert_eq!(magic(b"RIFF\0\0\0\0WAVEfmt "), Some(FileType::Audio));
+        assert_eq!(
+            magic(&[0x1A, 0x45, 0xDF, 0xA3, 0x01]),
+            Some(FileType::Video)
+        );
+        assert_eq!(magic(b"ID3\x04\0"), Some(FileType::Audio));
+        assert_eq!(magic(b"PK\x03\x04\x14\0"), Some(FileType::Archive));
+        assert_eq!(magic(&[0x1F, 0x8B, 0x08]), Some(FileType::Archive));
+        assert_eq!(
+            magic(b"\x89PNG\r\n\x1A\n\0\0\0\x0DIHDR"),
+            Some(FileType::Image)
+        );
+        assert_eq!(magic(b"GIF89a"), Some(FileType::Image));
+        assert_eq!(magic(b"hello"), None);
+        assert_eq!(magic(b""), None);
+    }
+
+    #[test]
+    fn magic_riff_needs_known_form() {
+        // WebP is a RIFF too, and left to the image format guessing
+        assert_eq!(magic(b"RIFF\0\0\0\0WEBPVP8 "), Some(FileType::Image));
+        assert_eq!(magic(b"RIFF\0\0\0\0????"), None);
+    }
+
+    #[test]
+    fn magic_ogg_goes_by_extension() {
+        let ogg = b"OggS\0\x02";
+        assert_eq!(by_magic(ogg, Path::new("a.ogv")), Some(FileType::Video));
+        assert_eq!(by_magic(ogg, Path::new("a.ogg")), Some(FileType::Audio));
+        assert_eq!(by_magic(ogg, Path::new("a")), Some(FileType::Audio));
+    }
+
+    #[test]
+    fn sniffing_text() {
+        assert_eq!(sniff_text(b"just some notes\r\n\tindented"), FileType::Text);
+        assert_eq!(sniff_text("ünïcödé".as_bytes()), FileType::Text);
+        // Cut off in the middle of a multibyte character
+        assert_eq!(sniff_text(&"aé".as_bytes()[..2]), FileType::Text);
+        assert_eq!(sniff_text(b"bin\0ary"), FileType::Other);
+        assert_eq!(sniff_text(b"\xFF\xFE\xFD not utf-8"), FileType::Other);
+        assert_eq!(sniff_text(b""), FileType::Other);
+    }
+}
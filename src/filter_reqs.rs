@@ -1,13 +1,110 @@
 use {
     crate::{
         collection::{Collection, Tags, TagsExt},
-        tag,
+        db::{EntryMap, EntrySet},
+        entry, phash, tag,
+    },
+    constcat::concat_slices,
+    std::{
+        borrow::Cow,
+        fmt,
+        ops::{Range, RangeInclusive},
+        path::Path,
     },
-    std::borrow::Cow,
     tagfilter_lang::Requirement,
     thiserror::Error,
 };
 
+/// Names of the filename function
+const FILENAME_FNS: &[&str] = &["filename", "file", "fname", "f"];
+const NTAGS_FN: &str = "ntags";
+const SAVED_FN: &str = "saved";
+const SIMILAR_FN: &str = "similar";
+const SIMILAR_ID_FN: &str = "similar-id";
+
+/// Functions whose parameters aren't tags, but paths, names or numbers
+pub const NON_TAG_PARAM_FNS: &[&str] = concat_slices!([""; &str]:
+    FILENAME_FNS,
+    &[NTAGS_FN, SAVED_FN, SIMILAR_FN, SIMILAR_ID_FN]
+);
+
+/// Lowercase `query`, except for the parameters of [`NON_TAG_PARAM_FNS`], which are kept as typed.
+///
+/// Tag names are lowercase, but paths can be case sensitive.
+pub fn lowercase_tags(query: &mut String) {
+    let mut out = String::with_capacity(query.len());
+    // For each open bracket, whether its parameters are kept as typed
+    let mut as_typed: Vec<bool> = Vec::new();
+    let mut last_fn = String::new();
+    let mut rest = query.as_str();
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            '@' => {
+                let len = rest[1..]
+                    .find(|c: char| matches!(c, '[' | ']' | '@') || c.is_whitespace())
+                    .map_or(rest.len(), |len| len + 1);
+                last_fn = rest[1..len].to_ascii_lowercase();
+                out.push('@');
+                out.push_str(&last_fn);
+                len
+            }
+            '[' => {
+                as_typed.push(
+                    as_typed.last() == Some(&true) || NON_TAG_PARAM_FNS.contains(&last_fn.as_str()),
+                );
+                last_fn.clear();
+                out.push(c);
+                1
+            }
+            ']' => {
+                as_typed.pop();
+                out.push(c);
+                1
+            }
+            _ => {
+                if c.is_whitespace() {
+                    last_fn.clear();
+                }
+                if as_typed.last() == Some(&true) {
+                    out.push(c);
+                } else {
+                    out.push(c.to_ascii_lowercase());
+                }
+                c.len_utf8()
+            }
+        };
+        rest = &rest[len..];
+    }
+    *query = out;
+}
+
+/// Rewrite the entry uids of the `@similar-id` calls in `query` according to `map`, which maps
+/// old entry ids to new ones.
+///
+/// Uids not in `map` are replaced by `deleted`, so they fail to resolve instead of referring
+/// to whatever entry gets their uid next.
+pub fn remap_similar_ids(query: &str, map: &EntryMap<entry::Id>) -> String {
+    let prefix = ["@", SIMILAR_ID_FN, "["].concat();
+    let mut out = String::with_capacity(query.len());
+    let mut rest = query;
+    while let Some(pos) = rest.find(&prefix) {
+        let (before, after) = rest.split_at(pos + prefix.len());
+        out.push_str(before);
+        let len = after
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(after.len());
+        let (uid, after) = after.split_at(len);
+        match uid.parse().ok().and_then(|uid| map.get(&entry::Id(uid))) {
+            Some(new_id) => out.push_str(&new_id.0.to_string()),
+            None if uid.is_empty() => {}
+            None => out.push_str("deleted"),
+        }
+        rest = after;
+    }
+    out.push_str(rest);
+    out
+}
+
 #[derive(Default, Debug, PartialEq)]
 pub struct Requirements {
     reqs: Vec<Req>,
@@ -27,11 +124,24 @@ impl Requirements {
         &mut self,
         requirements: Vec<Requirement<'src>>,
         coll: &Collection,
+    ) -> Result<(), ReqTransformError<'src>> {
+        self.resolve_inner(requirements, coll, &mut Vec::new())
+    }
+    /// `saved_stack` holds the names of the saved searches currently being resolved,
+    /// so we can detect cycles.
+    fn resolve_inner<'src>(
+        &mut self,
+        requirements: Vec<Requirement<'src>>,
+        coll: &Collection,
+        saved_stack: &mut Vec<String>,
     ) -> Result<(), ReqTransformError<'src>> {
         self.reqs.clear();
         for requirement in requirements {
-            self.reqs
-                .push(Req::from_tagfilter_lang_req(requirement, coll)?);
+            self.reqs.push(Req::from_tagfilter_lang_req(
+                requirement,
+                coll,
+                saved_stack,
+            )?);
         }
         Ok(())
     }
@@ -120,6 +230,88 @@ impl Requirements {
     pub fn is_empty(&self) -> bool {
         self.reqs.is_empty()
     }
+    pub fn iter(&self) -> impl Iterator<Item = &Req> {
+        self.reqs.iter()
+    }
+    pub fn push(&mut self, req: Req) {
+        self.reqs.push(req);
+    }
+    /// Get the group at `path`, where each element indexes into the group above it.
+    ///
+    /// An empty path refers to `self`. Negations are looked through.
+    /// Saved searches are resolved from their text, so they can't be reached.
+    pub fn group_at_mut(&mut self, path: &[usize]) -> Option<&mut Requirements> {
+        let Some((&idx, rest)) = path.split_first() else {
+            return Some(self);
+        };
+        match strip_not_mut(self.reqs.get_mut(idx)?) {
+            Req::Any(reqs) | Req::All(reqs) | Req::None(reqs) => reqs.group_at_mut(rest),
+            _ => None,
+        }
+    }
+    fn node_at_mut(&mut self, path: &[usize]) -> Option<&mut Req> {
+        let (&idx, parent) = path.split_last()?;
+        self.group_at_mut(parent)?.reqs.get_mut(idx)
+    }
+    /// Remove the requirement at `path` and return it
+    pub fn take_at(&mut self, path: &[usize]) -> Option<Req> {
+        let (&idx, parent) = path.split_last()?;
+        let group = self.group_at_mut(parent)?;
+        (idx < group.reqs.len()).then(|| group.reqs.remove(idx))
+    }
+    /// Wrap the requirement at `path` in a negation, or unwrap it if it's already negated
+    pub fn toggle_not_at(&mut self, path: &[usize]) {
+        if let Some(node) = self.node_at_mut(path) {
+            // Placeholder, gets overwritten right away
+            let req = std::mem::replace(node, Req::PartOfSeq);
+            *node = match req {
+                Req::Not(inner) => *inner,
+                req => Req::Not(Box::new(req)),
+            };
+        }
+    }
+    /// Change the kind of the group at `path`, keeping its contents
+    pub fn set_group_kind_at(&mut self, path: &[usize], kind: GroupKind) {
+        if let Some(node) = self.node_at_mut(path) {
+            let node = strip_not_mut(node);
+            if let Req::Any(reqs) | Req::All(reqs) | Req::None(reqs) = node {
+                let reqs = std::mem::take(reqs);
+                *node = kind.wrap(reqs);
+            }
+        }
+    }
+    /// Move the requirement at `from` to the end of the group at `to`.
+    ///
+    /// Returns whether anything was moved.
+    pub fn move_into(&mut self, from: &[usize], to: &[usize]) -> bool {
+        // Can't move a group into itself
+        if to.starts_with(from) || self.group_at_mut(to).is_none() {
+            return false;
+        }
+        let Some(req) = self.take_at(from) else {
+            return false;
+        };
+        let mut to = to.to_vec();
+        // Taking out `from` shifts its later siblings back by one
+        if let Some((&from_idx, from_parent)) = from.split_last()
+            && to.starts_with(from_parent)
+            && let Some(to_idx) = to.get_mut(from_parent.len())
+            && *to_idx > from_idx
+        {
+            *to_idx -= 1;
+        }
+        if let Some(group) = self.group_at_mut(&to) {
+            group.push(req);
+            return true;
+        }
+        // Put it back where it was, rather than losing it
+        if let Some((&from_idx, from_parent)) = from.split_last()
+            && let Some(group) = self.group_at_mut(from_parent)
+        {
+            group.reqs.insert(from_idx.min(group.reqs.len()), req);
+        }
+        false
+    }
     pub fn any(&self, f: impl FnMut(&Req) -> bool) -> bool {
         self.reqs.iter().any(f)
     }
@@ -157,7 +349,125 @@ pub enum Req {
     Not(Box<Req>),
     FilenameSub(String),
     PartOfSeq,
-    NTags(usize),
+    NTags(CountCmp),
+    /// The entry only has this tag through implication, not explicitly
+    Implied(tag::Id),
+    /// A saved search, resolved from the collection
+    Saved {
+        name: String,
+        reqs: Requirements,
+    },
+    /// Perceptually similar to an image
+    Similar {
+        to: SimilarTo,
+        max_dist: u32,
+        /// Worked out when resolving, so matching doesn't need the hashes
+        matches: EntrySet,
+    },
+}
+
+/// What a [`Req::Similar`] compares against
+#[derive(Debug, PartialEq)]
+pub enum SimilarTo {
+    /// An image path, relative to the collection root
+    Path(String),
+    /// An entry of the collection. Written as `@similar-id[uid]`, so paths with characters
+    /// the filter syntax can't hold don't need to be written out.
+    Entry(entry::Id),
+}
+
+/// The kind of a requirement group
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupKind {
+    Any,
+    All,
+    None,
+}
+
+impl GroupKind {
+    pub const ALL: [Self; 3] = [Self::Any, Self::All, Self::None];
+    pub fn wrap(self, reqs: Requirements) -> Req {
+        match self {
+            Self::Any => Req::Any(reqs),
+            Self::All => Req::All(reqs),
+            Self::None => Req::None(reqs),
+        }
+    }
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Any => "@any",
+            Self::All => "@all",
+            Self::None => "@none",
+        }
+    }
+}
+
+fn strip_not_mut(req: &mut Req) -> &mut Req {
+    match req {
+        Req::Not(inner) => strip_not_mut(inner),
+        _ => req,
+    }
+}
+
+/// Comparison against a count, like the number of tags an entry has
+#[derive(Debug, PartialEq)]
+pub enum CountCmp {
+    Eq(usize),
+    Lt(usize),
+    Le(usize),
+    Gt(usize),
+    Ge(usize),
+    Range(Range<usize>),
+    RangeInclusive(RangeInclusive<usize>),
+}
+
+impl CountCmp {
+    /// Parse things like `3`, `>3`, `<=1`, `2..5` or `2..=5`
+    fn parse(src: &str) -> Option<Self> {
+        if let Some((lo, hi)) = src.split_once("..=") {
+            return Some(Self::RangeInclusive(lo.parse().ok()?..=hi.parse().ok()?));
+        }
+        if let Some((lo, hi)) = src.split_once("..") {
+            return Some(Self::Range(lo.parse().ok()?..hi.parse().ok()?));
+        }
+        let cmp = if let Some(n) = src.strip_prefix(">=") {
+            Self::Ge(n.parse().ok()?)
+        } else if let Some(n) = src.strip_prefix("<=") {
+            Self::Le(n.parse().ok()?)
+        } else if let Some(n) = src.strip_prefix('>') {
+            Self::Gt(n.parse().ok()?)
+        } else if let Some(n) = src.strip_prefix('<') {
+            Self::Lt(n.parse().ok()?)
+        } else {
+            Self::Eq(src.strip_prefix('=').unwrap_or(src).parse().ok()?)
+        };
+        Some(cmp)
+    }
+    pub fn matches(&self, n: usize) -> bool {
+        match self {
+            Self::Eq(rhs) => n == *rhs,
+            Self::Lt(rhs) => n < *rhs,
+            Self::Le(rhs) => n <= *rhs,
+            Self::Gt(rhs) => n > *rhs,
+            Self::Ge(rhs) => n >= *rhs,
+            Self::Range(range) => range.contains(&n),
+            Self::RangeInclusive(range) => range.contains(&n),
+        }
+    }
+}
+
+impl fmt::Display for CountCmp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Eq(n) => write!(f, "{n}"),
+            Self::Lt(n) => write!(f, "<{n}"),
+            Self::Le(n) => write!(f, "<={n}"),
+            Self::Gt(n) => write!(f, ">{n}"),
+            Self::Ge(n) => write!(f, ">={n}"),
+            Self::Range(range) => write!(f, "{}..{}", range.start, range.end),
+            Self::RangeInclusive(range) => write!(f, "{}..={}", range.start(), range.end()),
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -170,12 +480,42 @@ pub enum ReqTransformError<'src> {
     MissingParameter,
     #[error("Invalid parameter")]
     InvalidParameter,
+    #[error("No such saved search: {0}")]
+    NoSuchSavedSearch(&'src str),
+    #[error("Saved search {0} refers to itself")]
+    SavedSearchCycle(&'src str),
+    #[error("In saved search {name}: {err}")]
+    InSavedSearch { name: &'src str, err: String },
+    #[error("Can't hash image {path}: {err}")]
+    Unhashable { path: &'src str, err: String },
+    #[error("No such entry: {0}")]
+    NoSuchEntry(&'src str),
+    #[error("Entry {0} isn't hashed yet")]
+    NotHashed(&'src str),
+}
+
+impl ReqTransformError<'_> {
+    /// Byte range of the offending token in `src`, if known
+    pub fn span(&self, src: &str) -> Option<Range<usize>> {
+        match self {
+            Self::UnknownFn { name }
+            | Self::NoSuchTag(name)
+            | Self::NoSuchSavedSearch(name)
+            | Self::SavedSearchCycle(name)
+            | Self::InSavedSearch { name, .. }
+            | Self::Unhashable { path: name, .. }
+            | Self::NoSuchEntry(name)
+            | Self::NotHashed(name) => src.substr_range(name),
+            Self::MissingParameter | Self::InvalidParameter => None,
+        }
+    }
 }
 
 impl Req {
     fn from_tagfilter_lang_req<'src>(
         tf_req: Requirement<'src>,
         coll: &Collection,
+        saved_stack: &mut Vec<String>,
     ) -> Result<Self, ReqTransformError<'src>> {
         let req = match tf_req {
             Requirement::Tag(name) => {
@@ -193,20 +533,20 @@ impl Req {
             Requirement::FnCall(call) => match call.name {
                 "any" => {
                     let mut reqs = Requirements::default();
-                    reqs.resolve(call.params, coll)?;
+                    reqs.resolve_inner(call.params, coll, saved_stack)?;
                     Req::Any(reqs)
                 }
                 "all" => {
                     let mut reqs = Requirements::default();
-                    reqs.resolve(call.params, coll)?;
+                    reqs.resolve_inner(call.params, coll, saved_stack)?;
                     Req::All(reqs)
                 }
                 "none" => {
                     let mut reqs = Requirements::default();
-                    reqs.resolve(call.params, coll)?;
+                    reqs.resolve_inner(call.params, coll, saved_stack)?;
                     Req::None(reqs)
                 }
-                "filename" | "file" | "fname" | "f" => {
+                name if FILENAME_FNS.contains(&name) => {
                     let filename_sub = match call.params.first() {
                         Some(param) => match param {
                             Requirement::Tag(tag) | Requirement::TagExact(tag) => tag,
@@ -216,28 +556,136 @@ impl Req {
                         },
                         None => return Err(ReqTransformError::MissingParameter),
                     };
-                    Req::FilenameSub((*filename_sub).to_owned())
+                    // Matched against the lowercased path
+                    Req::FilenameSub(filename_sub.to_lowercase())
                 }
                 "seq" | "sequence" => Req::PartOfSeq,
-                "notag" | "no-tag" | "untagged" => Req::NTags(0),
-                "ntags" => match call.params.first() {
+                "notag" | "no-tag" | "untagged" => Req::NTags(CountCmp::Eq(0)),
+                NTAGS_FN => match call.params.first() {
                     Some(Requirement::Tag(tag) | Requirement::TagExact(tag)) => {
-                        match tag.parse::<usize>() {
-                            Ok(n) => Req::NTags(n),
-                            Err(_) => return Err(ReqTransformError::InvalidParameter),
+                        match CountCmp::parse(tag) {
+                            Some(cmp) => Req::NTags(cmp),
+                            None => return Err(ReqTransformError::InvalidParameter),
                         }
                     }
                     Some(_) => return Err(ReqTransformError::InvalidParameter),
                     None => return Err(ReqTransformError::MissingParameter),
                 },
+                "implied" => match call.params.first() {
+                    Some(&(Requirement::Tag(name) | Requirement::TagExact(name))) => {
+                        let id = coll
+                            .resolve_tag(name)
+                            .ok_or(ReqTransformError::NoSuchTag(name))?;
+                        Req::Implied(id)
+                    }
+                    Some(_) => return Err(ReqTransformError::InvalidParameter),
+                    None => return Err(ReqTransformError::MissingParameter),
+                },
+                SAVED_FN => {
+                    let name = match call.params.first() {
+                        Some(&(Requirement::Tag(name) | Requirement::TagExact(name))) => name,
+                        Some(_) => return Err(ReqTransformError::InvalidParameter),
+                        None => return Err(ReqTransformError::MissingParameter),
+                    };
+                    // Names are saved lowercase, but the parameter is kept as typed
+                    let key = name.to_ascii_lowercase();
+                    let query = coll
+                        .saved_searches
+                        .get(&key)
+                        .ok_or(ReqTransformError::NoSuchSavedSearch(name))?;
+                    if saved_stack.contains(&key) {
+                        return Err(ReqTransformError::SavedSearchCycle(name));
+                    }
+                    saved_stack.push(key.clone());
+                    let mut reqs = Requirements::default();
+                    let result = match tagfilter_lang::parse(query) {
+                        Ok(parsed) => reqs
+                            .resolve_inner(parsed, coll, saved_stack)
+                            .map_err(|e| e.to_string()),
+                        Err(e) => Err(e.to_string()),
+                    };
+                    saved_stack.pop();
+                    if let Err(err) = result {
+                        return Err(ReqTransformError::InSavedSearch { name, err });
+                    }
+                    Req::Saved { name: key, reqs }
+                }
+                SIMILAR_FN => {
+                    let path = match call.params.first() {
+                        Some(&(Requirement::Tag(path) | Requirement::TagExact(path))) => path,
+                        Some(_) => return Err(ReqTransformError::InvalidParameter),
+                        None => return Err(ReqTransformError::MissingParameter),
+                    };
+                    let max_dist = match call.params.get(1) {
+                        Some(Requirement::Tag(dist) | Requirement::TagExact(dist)) => dist
+                            .parse()
+                            .map_err(|_| ReqTransformError::InvalidParameter)?,
+                        Some(_) => return Err(ReqTransformError::InvalidParameter),
+                        None => phash::DEFAULT_MAX_DIST,
+                    };
+                    let hash = coll.phash_of_path(Path::new(path)).map_err(|e| {
+                        ReqTransformError::Unhashable {
+                            path,
+                            err: e.to_string(),
+                        }
+                    })?;
+                    Req::Similar {
+                        to: SimilarTo::Path(path.to_owned()),
+                        max_dist,
+                        matches: coll
+                            .similar_entries(hash, max_dist)
+                            .into_iter()
+                            .map(|(id, _)| id)
+                            .collect(),
+                    }
+                }
+                SIMILAR_ID_FN => {
+                    let uid = match call.params.first() {
+                        Some(&(Requirement::Tag(uid) | Requirement::TagExact(uid))) => uid,
+                        Some(_) => return Err(ReqTransformError::InvalidParameter),
+                        None => return Err(ReqTransformError::MissingParameter),
+                    };
+                    let id = entry::Id(
+                        uid.parse()
+                            .map_err(|_| ReqTransformError::InvalidParameter)?,
+                    );
+                    let max_dist = match call.params.get(1) {
+                        Some(Requirement::Tag(dist) | Requirement::TagExact(dist)) => dist
+                            .parse()
+                            .map_err(|_| ReqTransformError::InvalidParameter)?,
+                        Some(_) => return Err(ReqTransformError::InvalidParameter),
+                        None => phash::DEFAULT_MAX_DIST,
+                    };
+                    if !coll.entries.contains_key(&id) {
+                        return Err(ReqTransformError::NoSuchEntry(uid));
+                    }
+                    let hash = coll
+                        .perceptual_hashes
+                        .get(&id)
+                        .ok_or(ReqTransformError::NotHashed(uid))?
+                        .hash;
+                    Req::Similar {
+                        to: SimilarTo::Entry(id),
+                        max_dist,
+                        matches: coll
+                            .similar_entries(hash, max_dist)
+                            .into_iter()
+                            .map(|(id, _)| id)
+                            .collect(),
+                    }
+                }
                 _ => return Err(ReqTransformError::UnknownFn { name: call.name }),
             },
-            Requirement::Not(req) => Req::Not(Box::new(Req::from_tagfilter_lang_req(*req, coll)?)),
+            Requirement::Not(req) => Req::Not(Box::new(Req::from_tagfilter_lang_req(
+                *req,
+                coll,
+                saved_stack,
+            )?)),
         };
         Ok(req)
     }
 
-    fn to_string<'a>(&self, tags: &'a Tags) -> Cow<'a, str> {
+    pub(crate) fn to_string<'a>(&self, tags: &'a Tags) -> Cow<'a, str> {
         match self {
             Req::Any(reqs) => format!("@any[{}]", reqs.to_string(tags)).into(),
             Req::All(reqs) => format!("@all[{}]", reqs.to_string(tags)).into(),
@@ -247,8 +695,21 @@ impl Req {
             Req::Not(req) => format!("!{}", req.to_string(tags)).into(),
             Req::FilenameSub(substr) => format!("@f[{substr}]").into(),
             Req::PartOfSeq => "@seq".into(),
-            Req::NTags(0) => "@untagged".into(),
-            Req::NTags(n) => format!("@ntags[{n}]").into(),
+            Req::NTags(CountCmp::Eq(0)) => "@untagged".into(),
+            Req::NTags(cmp) => format!("@ntags[{cmp}]").into(),
+            Req::Implied(id) => format!("@implied[{}]", tags.first_name_of(id)).into(),
+            Req::Saved { name, .. } => format!("@saved[{name}]").into(),
+            Req::Similar { to, max_dist, .. } => {
+                let (name, param) = match to {
+                    SimilarTo::Path(path) => (SIMILAR_FN, path.clone()),
+                    SimilarTo::Entry(id) => (SIMILAR_ID_FN, id.0.to_string()),
+                };
+                if *max_dist == phash::DEFAULT_MAX_DIST {
+                    format!("@{name}[{param}]").into()
+                } else {
+                    format!("@{name}[{param} {max_dist}]").into()
+                }
+            }
         }
     }
 }
@@ -261,6 +722,16 @@ pub enum ParseResolveError<'a> {
     ReqTransform(ReqTransformError<'a>),
 }
 
+impl ParseResolveError<'_> {
+    /// Byte range of the offending token in `src`, if known
+    pub fn span(&self, src: &str) -> Option<Range<usize>> {
+        match self {
+            Self::Parse(_) => None,
+            Self::ReqTransform(e) => e.span(src),
+        }
+    }
+}
+
 impl<'a> From<ReqTransformError<'a>> for ParseResolveError<'a> {
     fn from(src: ReqTransformError<'a>) -> Self {
         Self::ReqTransform(src)
@@ -272,3 +743,49 @@ impl<'a> From<tagfilter_lang::ParseError<'a>> for ParseResolveError<'a> {
         Self::Parse(src)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::CountCmp;
+
+    #[test]
+    fn count_cmp_parse() {
+        assert_eq!(CountCmp::parse("3"), Some(CountCmp::Eq(3)));
+        assert_eq!(CountCmp::parse("=3"), Some(CountCmp::Eq(3)));
+        assert_eq!(CountCmp::parse("<3"), Some(CountCmp::Lt(3)));
+        assert_eq!(CountCmp::parse("<=3"), Some(CountCmp::Le(3)));
+        assert_eq!(CountCmp::parse(">3"), Some(CountCmp::Gt(3)));
+        assert_eq!(CountCmp::parse(">=3"), Some(CountCmp::Ge(3)));
+        assert_eq!(CountCmp::parse("2..5"), Some(CountCmp::Range(2..5)));
+        assert_eq!(
+            CountCmp::parse("2..=5"),
+            Some(CountCmp::RangeInclusive(2..=5))
+        );
+    }
+
+    #[test]
+    fn count_cmp_parse_invalid() {
+        for src in ["", "x", ">", "=>3", "-1", "2..", "..5", "2..=x", ">=3.5"] {
+            assert_eq!(CountCmp::parse(src), None, "{src:?}");
+        }
+    }
+
+    #[test]
+    fn count_cmp_matches() {
+        let matching = |cmp: CountCmp| (0..8).filter(|&n| cmp.matches(n)).collect::<Vec<_>>();
+        assert_eq!(matching(CountCmp::Eq(0)), [0]);
+        assert_eq!(matching(CountCmp::Lt(2)), [0, 1]);
+        assert_eq!(matching(CountCmp::Le(2)), [0, 1, 2]);
+        assert_eq!(matching(CountCmp::Gt(5)), [6, 7]);
+        assert_eq!(matching(CountCmp::Ge(6)), [6, 7]);
+        assert_eq!(matching(CountCmp::Range(2..4)), [2, 3]);
+        assert_eq!(matching(CountCmp::RangeInclusive(2..=4)), [2, 3, 4]);
+    }
+
+    #[test]
+    fn count_cmp_display_round_trips() {
+        for src in ["3", "<3", "<=3", ">3", ">=3", "2..5", "2..=5"] {
+            assert_eq!(CountCmp::parse(src).unwrap().to_string(), src);
+        }
+    }
+}
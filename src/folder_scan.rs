@@ -1,5 +1,406 @@
-use {std::path::Path, walkdir::WalkDir};
+use {
+    crate::{
+        archive,
+        collection::{self, ArchivePolicy, LinkPolicy},
+        db::FolderChanges,
+        dlog, entry,
+    },
+    anyhow::Context,
+    fnv::{FnvHashMap, FnvHashSet},
+    ignore::{
+        gitignore::{Gitignore, GitignoreBuilder},
+        Match, WalkBuilder, WalkState,
+    },
+    parking_lot::Mutex,
+    std::{
+        ffi::OsStr,
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc, Arc,
+        },
+        thread::JoinHandle,
+        time::SystemTime,
+    },
+};
 
-pub fn walkdir(root: &Path) -> WalkDir {
-    WalkDir::new(root).sort_by(|a, b| a.file_name().cmp(b.file_name()))
+/// Whether `path` has one of the extensions in `ignored_extensions`
+pub fn has_ignored_ext(path: &Path, ignored_extensions: &[String]) -> bool {
+    path.extension().is_some_and(|ext| {
+        ignored_extensions
+            .iter()
+            .any(|ign_ext| ext == AsRef::<OsStr>::as_ref(ign_ext))
+    })
+}
+
+/// Name of the gitignore-style files that can be placed anywhere in a collection folder
+pub const IGNORE_FILENAME: &str = ".cowbumpignore";
+
+/// Ignore patterns new collections start out with.
+///
+/// Skips git repos, Synology thumbnail folders and hidden files.
+pub const DEFAULT_IGNORE_PATTERNS: &[&str] = &[".git/", "@eaDir/", ".*"];
+
+/// Build a matcher out of collection-level gitignore-style patterns, relative to `root`
+pub fn build_ignore_matcher(root: &Path, patterns: &[String]) -> anyhow::Result<Gitignore> {
+    let mut builder = GitignoreBuilder::new(root);
+    for pat in patterns {
+        builder
+            .add_line(None, pat)
+            .with_context(|| format!("Invalid ignore pattern `{pat}`"))?;
+    }
+    Ok(builder.build()?)
+}
+
+/// Ignore pattern matching exactly `rel_path`, relative to the collection root
+pub fn literal_ignore_pattern(rel_path: &Path) -> String {
+    let mut pat = String::new();
+    for comp in rel_path.components() {
+        pat.push('/');
+        for c in comp.as_os_str().to_string_lossy().chars() {
+            if matches!(c, '*' | '?' | '[' | ']' | '{' | '}' | '\\' | '!' | '#') {
+                pat.push('\\');
+            }
+            pat.push(c);
+        }
+    }
+    pat
+}
+
+/// Walker for `start` (`coll_root` or a folder under it) that skips ignored paths
+pub fn walk_builder(
+    coll_root: &Path,
+    start: &Path,
+    ignore_patterns: &[String],
+    link_policy: LinkPolicy,
+) -> anyhow::Result<WalkBuilder> {
+    let matcher = build_ignore_matcher(coll_root, ignore_patterns)?;
+    let mut builder = WalkBuilder::new(start);
+    builder
+        .standard_filters(false)
+        .follow_links(link_policy.follow_symlinks)
+        .add_custom_ignore_filename(IGNORE_FILENAME)
+        .filter_entry(move |dir_entry| {
+            let is_dir = dir_entry.file_type().is_some_and(|ft| ft.is_dir());
+            dir_entry.file_name() != IGNORE_FILENAME
+                && !matcher.matched(dir_entry.path(), is_dir).is_ignore()
+        });
+    Ok(builder)
+}
+
+/// Whether the walk error is a symlinked folder pointing back at one of its ancestors
+pub fn is_loop_err(err: &ignore::Error) -> bool {
+    match err {
+        ignore::Error::Loop { .. } => true,
+        ignore::Error::WithPath { err, .. }
+        | ignore::Error::WithDepth { err, .. }
+        | ignore::Error::WithLineNumber { err, .. } => is_loop_err(err),
+        _ => false,
+    }
+}
+
+/// Identifies a file on disk, no matter which link it's reached through
+pub type FileId = (u64, u64);
+
+/// Device and inode of the file `path` leads to, following symlinks
+#[cfg(unix)]
+pub fn file_id(path: &Path) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.dev(), meta.ino()))
+}
+
+/// No stable way to get at file identity here, so nothing gets deduplicated
+#[cfg(not(unix))]
+pub fn file_id(_path: &Path) -> Option<FileId> {
+    None
+}
+
+/// Remembers the file ids of paths, relative to the collection root, between folder changes
+#[derive(Default)]
+pub struct FileIdCache {
+    ids: FnvHashMap<PathBuf, Option<FileId>>,
+}
+
+impl FileIdCache {
+    /// File id of `path`, only hitting the disk the first time it's asked for
+    pub fn get(&mut self, root: &Path, path: &Path) -> Option<FileId> {
+        if let Some(id) = self.ids.get(path) {
+            return *id;
+        }
+        let id = file_id(&root.join(path));
+        self.ids.insert(path.to_owned(), id);
+        id
+    }
+    /// Forget `path` and everything under it, because it changed on disk
+    pub fn forget(&mut self, path: &Path) {
+        self.ids.retain(|p, _| !p.starts_with(path));
+    }
+}
+
+/// Checks single paths against the ignore rules, for when we can't walk the folder
+pub struct IgnoreChecker<'a> {
+    root: &'a Path,
+    coll_matcher: Gitignore,
+}
+
+impl<'a> IgnoreChecker<'a> {
+    pub fn new(root: &'a Path, ignore_patterns: &[String]) -> anyhow::Result<Self> {
+        Ok(Self {
+            root,
+            coll_matcher: build_ignore_matcher(root, ignore_patterns)?,
+        })
+    }
+    /// Whether `rel_path` (relative to root) or any of its parent folders is ignored
+    pub fn is_ignored(&self, rel_path: &Path, is_dir: bool) -> bool {
+        if rel_path
+            .file_name()
+            .is_some_and(|name| name == IGNORE_FILENAME)
+            || self
+                .coll_matcher
+                .matched_path_or_any_parents(rel_path, is_dir)
+                .is_ignore()
+        {
+            return true;
+        }
+        let abs_path = self.root.join(rel_path);
+        // Deeper ignore files take precedence, same as when walking
+        for dir in rel_path.ancestors().skip(1) {
+            let ignore_file = self.root.join(dir).join(IGNORE_FILENAME);
+            if !ignore_file.is_file() {
+                continue;
+            }
+            let (matcher, err) = Gitignore::new(&ignore_file);
+            if let Some(e) = err {
+                dlog!("Error reading {ignore_file:?}: {e}");
+            }
+            match matcher.matched_path_or_any_parents(&abs_path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        false
+    }
+}
+
+/// Progress of a folder scan, shared with the scanning threads
+#[derive(Default)]
+pub struct ScanProgress {
+    pub files_walked: AtomicUsize,
+}
+
+/// Walk `root` on multiple threads, collecting the paths of all files relative to `root`.
+///
+/// Paths matched by `ignore_patterns` or by `.cowbumpignore` files are skipped.
+/// If the link policy deduplicates, the file ids of the paths are collected as well.
+pub fn walk_files_parallel(
+    root: &Path,
+    ignore_patterns: &[String],
+    link_policy: LinkPolicy,
+    progress: &ScanProgress,
+) -> anyhow::Result<FnvHashMap<PathBuf, Option<FileId>>> {
+    let (sender, receiver) = mpsc::channel();
+    let first_err = Mutex::new(None);
+    walk_builder(root, root, ignore_patterns, link_policy)?
+        .build_parallel()
+        .run(|| {
+            let sender = sender.clone();
+            let first_err = &first_err;
+            Box::new(move |result| {
+                let dir_entry = match result {
+                    Ok(dir_entry) => dir_entry,
+                    Err(e) if is_loop_err(&e) => {
+                        dlog!("Skipping symlink loop: {e}");
+                        return WalkState::Continue;
+                    }
+                    Err(e) => {
+                        first_err.lock().get_or_insert(e);
+                        return WalkState::Quit;
+                    }
+                };
+                if dir_entry.file_type().is_some_and(|ft| ft.is_dir()) {
+                    return WalkState::Continue;
+                }
+                match dir_entry.path().strip_prefix(root) {
+                    Ok(stripped) => {
+                        progress.files_walked.fetch_add(1, Ordering::Relaxed);
+                        let id = if link_policy.dedup_inodes {
+                            file_id(dir_entry.path())
+                        } else {
+                            None
+                        };
+                        // Receiver outlives the walk, can't fail
+                        let _ = sender.send((stripped.to_owned(), id));
+                    }
+                    Err(e) => {
+                        dlog!("Failed to add entry {:?}: {e}", dir_entry.path());
+                    }
+                }
+                WalkState::Continue
+            })
+        });
+    drop(sender);
+    if let Some(e) = first_err.into_inner() {
+        return Err(e.into());
+    }
+    Ok(receiver.into_iter().collect())
+}
+
+/// Diff the files under `root` against the paths a collection already has
+pub fn scan_changes(
+    root: &Path,
+    known_paths: &FnvHashSet<PathBuf>,
+    ignored_extensions: &[String],
+    ignore_patterns: &[String],
+    link_policy: LinkPolicy,
+    archive_policy: ArchivePolicy,
+    progress: &ScanProgress,
+) -> anyhow::Result<FolderChanges> {
+    let mut on_disk = walk_files_parallel(root, ignore_patterns, link_policy, progress)?;
+    // Turning expanding off shouldn't throw away the entries of archives that were expanded,
+    // along with their tags
+    let has_members: FnvHashSet<&Path> = known_paths
+        .iter()
+        .filter_map(|path| archive::split_member(path).map(|(archive, _)| archive))
+        .collect();
+    let expanded = expand_archives(root, &mut on_disk, ignored_extensions, |path| {
+        archive_policy.expand || has_members.contains(path)
+    });
+    let mut add: Vec<PathBuf> = on_disk
+        .keys()
+        .filter(|path| !known_paths.contains(*path) && !has_ignored_ext(path, ignored_extensions))
+        .cloned()
+        .collect();
+    add.sort();
+    if link_policy.dedup_inodes {
+        // Files the collection already has claim their id first
+        let mut claimed: FnvHashSet<FileId> = on_disk
+            .iter()
+            .filter(|(path, _)| known_paths.contains(*path))
+            .filter_map(|(_, id)| *id)
+            .collect();
+        add.retain(|path| on_disk[path].is_none_or(|id| claimed.insert(id)));
+    }
+    // Entries that got ignored after being added still exist, so they're not removed.
+    // Archives that got expanded are replaced by their members.
+    let mut remove: Vec<PathBuf> = known_paths
+        .iter()
+        .filter(|path| {
+            expanded.contains(*path) || (!on_disk.contains_key(*path) && !root.join(path).exists())
+        })
+        .cloned()
+        .collect();
+    remove.sort();
+    Ok(FolderChanges {
+        add,
+        remove,
+        rename: Vec::new(),
+        stale_phashes: Vec::new(),
+    })
+}
+
+/// The entries of `hashed` whose file under `root` has a different modification time than
+/// the one recorded with their hash
+fn stale_phashes(
+    root: &Path,
+    hashed: &[(entry::Id, PathBuf, Option<SystemTime>)],
+) -> Vec<entry::Id> {
+    hashed
+        .iter()
+        .filter(|(_, path, mtime)| archive::mtime(&root.join(path)) != *mtime)
+        .map(|(id, ..)| *id)
+        .collect()
+}
+
+/// Replace the expandable archives in `on_disk` that `should_expand` agrees with by their image
+/// members.
+///
+/// Archives that can't be read are kept as they are. Returns the expanded archives.
+fn expand_archives(
+    root: &Path,
+    on_disk: &mut FnvHashMap<PathBuf, Option<FileId>>,
+    ignored_extensions: &[String],
+    should_expand: impl Fn(&Path) -> bool,
+) -> FnvHashSet<PathBuf> {
+    let archives: Vec<PathBuf> = on_disk
+        .keys()
+        .filter(|path| {
+            archive::is_expandable(path)
+                && !has_ignored_ext(path, ignored_extensions)
+                && should_expand(path)
+        })
+        .cloned()
+        .collect();
+    let mut expanded = FnvHashSet::default();
+    for path in archives {
+        match archive::expand(root, &path) {
+            Ok(members) => {
+                on_disk.remove(&path);
+                // Members don't have their own file ids, so they never count as duplicates
+                on_disk.extend(members.into_iter().map(|member| (member, None)));
+                expanded.insert(path);
+            }
+            Err(e) => dlog!("Failed to read archive {path:?}: {e}"),
+        }
+    }
+    expanded
+}
+
+/// A folder scan running in the background
+pub struct ChangesScan {
+    /// The collection being scanned
+    pub coll_id: collection::Id,
+    pub progress: Arc<ScanProgress>,
+    handle: Option<JoinHandle<anyhow::Result<FolderChanges>>>,
+}
+
+impl ChangesScan {
+    /// `hashed` are the entries with a cached perceptual hash, along with the modification time
+    /// recorded with it. The ones that changed since end up in
+    /// [`FolderChanges::stale_phashes`].
+    #[expect(clippy::too_many_arguments)]
+    pub fn start(
+        coll_id: collection::Id,
+        root: PathBuf,
+        known_paths: FnvHashSet<PathBuf>,
+        hashed: Vec<(entry::Id, PathBuf, Option<SystemTime>)>,
+        ignored_extensions: Vec<String>,
+        ignore_patterns: Vec<String>,
+        link_policy: LinkPolicy,
+        archive_policy: ArchivePolicy,
+    ) -> Self {
+        let progress = Arc::new(ScanProgress::default());
+        let progress_clone = Arc::clone(&progress);
+        let handle = std::thread::spawn(move || {
+            let mut changes = scan_changes(
+                &root,
+                &known_paths,
+                &ignored_extensions,
+                &ignore_patterns,
+                link_policy,
+                archive_policy,
+                &progress_clone,
+            )?;
+            changes.stale_phashes = stale_phashes(&root, &hashed);
+            Ok(changes)
+        });
+        Self {
+            coll_id,
+            progress,
+            handle: Some(handle),
+        }
+    }
+    /// Returns the result once the scan is done
+    pub fn poll(&mut self) -> Option<anyhow::Result<FolderChanges>> {
+        if !self.handle.as_ref()?.is_finished() {
+            return None;
+        }
+        let handle = self.handle.take()?;
+        Some(
+            handle
+                .join()
+                .unwrap_or_else(|_| Err(anyhow::anyhow!("Folder scan thread panicked"))),
+        )
+    }
 }
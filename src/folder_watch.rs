@@ -0,0 +1,155 @@
+//! Watching the active collection's folder for changes while the app is running
+
+use {
+    crate::{dlog, folder_scan::FileIdCache},
+    fnv::FnvHashSet,
+    notify::{
+        event::{ModifyKind, RenameMode},
+        EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+    },
+    std::{
+        path::{Path, PathBuf},
+        sync::mpsc::{self, Receiver},
+        time::{Duration, Instant},
+    },
+};
+
+/// How long the folder has to be quiet before a batch of changes is reported
+const BATCH_DELAY: Duration = Duration::from_millis(500);
+
+pub struct FolderWatcher {
+    root: PathBuf,
+    /// Watching stops when this is dropped
+    _watcher: RecommendedWatcher,
+    rx: Receiver<notify::Result<notify::Event>>,
+    /// Paths (relative to root) touched since the last batch
+    touched: FnvHashSet<PathBuf>,
+    /// Renames (relative to root) since the last batch, old path first
+    renames: Vec<(PathBuf, PathBuf)>,
+    /// The old (absolute) path of a rename whose new path hasn't been reported yet, with its
+    /// tracker
+    rename_from: Option<(Option<usize>, PathBuf)>,
+    last_event: Option<Instant>,
+    /// File ids of the collection's entries, so they don't all get stat'd on every batch
+    pub file_ids: FileIdCache,
+}
+
+/// What happened in the folder since the last batch, relative to the root
+#[derive(Default)]
+pub struct TouchedPaths {
+    /// Paths that were created, removed or modified in some way
+    pub paths: Vec<PathBuf>,
+    /// Paths that were renamed, old path first. Their paths aren't in `paths`.
+    pub renames: Vec<(PathBuf, PathBuf)>,
+}
+
+impl FolderWatcher {
+    pub fn new(root: &Path) -> notify::Result<Self> {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+        watcher.watch(root, RecursiveMode::Recursive)?;
+        Ok(Self {
+            root: root.to_owned(),
+            _watcher: watcher,
+            rx,
+            touched: FnvHashSet::default(),
+            renames: Vec::new(),
+            rename_from: None,
+            last_event: None,
+            file_ids: FileIdCache::default(),
+        })
+    }
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+    /// Collect pending events.
+    ///
+    /// Once the folder has been quiet for a while, returns the paths touched since the last batch.
+    /// It's up to the caller to figure out what actually changed.
+    pub fn poll(&mut self) -> Option<TouchedPaths> {
+        for result in self.rx.try_iter() {
+            let event = match result {
+                Ok(event) => event,
+                Err(e) => {
+                    dlog!("Folder watch error: {e}");
+                    continue;
+                }
+            };
+            if let EventKind::Modify(ModifyKind::Name(mode)) = event.kind {
+                self.on_rename(mode, event.attrs.tracker(), &event.paths);
+            }
+            if !matches!(
+                event.kind,
+                EventKind::Create(_)
+                    | EventKind::Remove(_)
+                    | EventKind::Modify(ModifyKind::Name(_) | ModifyKind::Any)
+            ) {
+                continue;
+            }
+            for path in event.paths {
+                if let Ok(rel) = path.strip_prefix(&self.root)
+                    && !rel.as_os_str().is_empty()
+                {
+                    self.touched.insert(rel.to_owned());
+                }
+            }
+            self.last_event = Some(Instant::now());
+        }
+        if self
+            .last_event
+            .is_some_and(|last| last.elapsed() >= BATCH_DELAY)
+        {
+            self.last_event = None;
+            // Unpaired renames stay in `touched`, and are seen as a remove and an add
+            self.rename_from = None;
+            let renames = std::mem::take(&mut self.renames);
+            for (from, to) in &renames {
+                self.touched.remove(from);
+                self.touched.remove(to);
+            }
+            return Some(TouchedPaths {
+                paths: self.touched.drain().collect(),
+                renames,
+            });
+        }
+        None
+    }
+    /// Pair up the old and new paths of renames.
+    ///
+    /// Some platforms report both paths in one event, others in two events in a row, with or
+    /// without a tracker tying them together.
+    fn on_rename(&mut self, mode: RenameMode, tracker: Option<usize>, paths: &[PathBuf]) {
+        match (mode, paths) {
+            (RenameMode::Both, [from, to]) => {
+                self.push_rename(from, to);
+            }
+            (RenameMode::From, [from]) => {
+                self.rename_from = Some((tracker, from.clone()));
+            }
+            (RenameMode::To, [to]) => {
+                if let Some((from_tracker, from)) = self.rename_from.take()
+                    && from_tracker == tracker
+                {
+                    self.push_rename(&from, to);
+                }
+            }
+            _ => {}
+        }
+    }
+    fn push_rename(&mut self, from: &Path, to: &Path) {
+        let (Some(from), Some(to)) = (self.rel(from), self.rel(to)) else {
+            return;
+        };
+        // With `Both`, the separate `From` and `To` events may have been paired already
+        if !self.renames.contains(&(from.clone(), to.clone())) {
+            self.renames.push((from, to));
+        }
+    }
+    /// `path` relative to the root, `None` if it's the root itself or outside of it
+    fn rel(&self, path: &Path) -> Option<PathBuf> {
+        path.strip_prefix(&self.root)
+            .ok()
+            .filter(|rel| !rel.as_os_str().is_empty())
+            .map(Path::to_owned)
+    }
+}
@@ -0,0 +1,323 @@
+//! Checking a collection for inconsistencies, and repairing them
+//!
+//! These can come from bugs, hand-edited databases, or restoring old backups.
+
+use {
+    crate::{
+        collection::{Collection, TagsExt},
+        db::{Db, Uid},
+        entry,
+        preferences::{AppId, AppMapExt},
+        sequence, tag,
+    },
+    fnv::FnvHashMap,
+    std::path::PathBuf,
+};
+
+pub enum Problem {
+    DanglingEntryTag {
+        entry: entry::Id,
+        tag: tag::Id,
+    },
+    DanglingImply {
+        tag: tag::Id,
+        implied: tag::Id,
+    },
+    DanglingSeqEntry {
+        seq: sequence::Id,
+        entry: entry::Id,
+    },
+    /// Tag specific app for a tag that doesn't exist
+    DanglingAppTag {
+        tag: tag::Id,
+    },
+    /// Tag specific app that doesn't exist
+    DanglingApp {
+        tag: tag::Id,
+        app: AppId,
+    },
+    /// Several entries with the same path. The first one is kept on repair.
+    DuplicatePath {
+        path: PathBuf,
+        ids: Vec<entry::Id>,
+    },
+    NamelessTag {
+        tag: tag::Id,
+    },
+    /// Uids in use that the counter would hand out again
+    UidsAboveCounter {
+        max: Uid,
+    },
+}
+
+impl Problem {
+    pub fn description(&self, coll: &Collection, db: &Db) -> String {
+        let path_of = |id: &entry::Id| match coll.entries.get(id) {
+            Some(en) => en.path.display().to_string(),
+            None => format!("<dangling:{id:?}>"),
+        };
+        match self {
+            Self::DanglingEntryTag { entry, tag } => {
+                format!("Entry {} has nonexistent tag {tag:?}", path_of(entry))
+            }
+            Self::DanglingImply { tag, implied } => format!(
+                "Tag {} implies nonexistent tag {implied:?}",
+                coll.tags.first_name_of(tag)
+            ),
+            Self::DanglingSeqEntry { seq, entry } => format!(
+                "Sequence {} contains nonexistent entry {entry:?}",
+                coll.sequences
+                    .get(seq)
+                    .map_or("<dangling>", |seq| seq.name.as_str())
+            ),
+            Self::DanglingAppTag { tag } => {
+                format!("Nonexistent tag {tag:?} has a specific app")
+            }
+            Self::DanglingApp { tag, app } => format!(
+                "Tag {} has nonexistent specific app {}",
+                coll.tags.first_name_of(tag),
+                db.preferences.applications.name_of(app)
+            ),
+            Self::DuplicatePath { path, ids } => {
+                format!("{} entries for {}", ids.len(), path.display())
+            }
+            Self::NamelessTag { tag } => format!("Tag {tag:?} has no names"),
+            Self::UidsAboveCounter { max } => format!(
+                "Uids up to {max} are in use, but the counter is at {}",
+                db.uid_counter.peek()
+            ),
+        }
+    }
+    pub fn repair_label(&self) -> &'static str {
+        match self {
+            Self::DanglingEntryTag { .. } | Self::DanglingImply { .. } => "Remove tag",
+            Self::DanglingSeqEntry { .. } => "Remove from sequence",
+            Self::DanglingAppTag { .. } | Self::DanglingApp { .. } => "Remove app",
+            Self::DuplicatePath { .. } => "Merge entries",
+            Self::NamelessTag { .. } => "Give placeholder name",
+            Self::UidsAboveCounter { .. } => "Advance counter",
+        }
+    }
+    pub fn repair(&self, coll: &mut Collection, db: &mut Db) -> anyhow::Result<()> {
+        match self {
+            Self::DanglingEntryTag { entry, tag } => {
+                if let Some(en) = coll.entries.get_mut(entry) {
+                    en.tags.remove(tag);
+                }
+            }
+            Self::DanglingImply { tag, implied } => {
+                if let Some(tag) = coll.tags.get_mut(tag) {
+                    tag.implies.remove(implied);
+                }
+            }
+            Self::DanglingSeqEntry { seq, entry } => {
+                if let Some(seq) = coll.sequences.get_mut(seq) {
+                    seq.entries.retain(|id| id != entry);
+                }
+            }
+            Self::DanglingAppTag { tag } | Self::DanglingApp { tag, .. } => {
+                coll.tag_specific_apps.remove(tag);
+            }
+            Self::DuplicatePath { ids, .. } => {
+                if let [keep, others @ ..] = &ids[..] {
+                    coll.merge_duplicates(*keep, others)?;
+                }
+            }
+            Self::NamelessTag { tag } => {
+                if let Some(tag_data) = coll.tags.get_mut(tag) {
+                    tag_data.names.push(format!("unnamed-{}", tag.0));
+                }
+            }
+            Self::UidsAboveCounter { max } => db.uid_counter.bump_past(*max),
+        }
+        Ok(())
+    }
+}
+
+/// Find the problems in `coll`, which is one of the collections of `db`
+pub fn check(coll: &Collection, db: &Db) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut entry_ids: Vec<&entry::Id> = coll.entries.keys().collect();
+    entry_ids.sort_by_key(|id| id.0);
+    for id in &entry_ids {
+        let mut tags: Vec<&tag::Id> = coll.entries[id].tags.iter().collect();
+        tags.sort_by_key(|id| id.0);
+        for tag in tags {
+            if !coll.tags.contains_key(tag) {
+                problems.push(Problem::DanglingEntryTag {
+                    entry: **id,
+                    tag: *tag,
+                });
+            }
+        }
+    }
+    let mut tag_ids: Vec<&tag::Id> = coll.tags.keys().collect();
+    tag_ids.sort_by_key(|id| id.0);
+    for id in &tag_ids {
+        let tag = &coll.tags[id];
+        if tag.names.is_empty() {
+            problems.push(Problem::NamelessTag { tag: **id });
+        }
+        for implied in &tag.implies {
+            if !coll.tags.contains_key(implied) {
+                problems.push(Problem::DanglingImply {
+                    tag: **id,
+                    implied: *implied,
+                });
+            }
+        }
+    }
+    let mut seq_ids: Vec<&sequence::Id> = coll.sequences.keys().collect();
+    seq_ids.sort_by_key(|id| id.0);
+    for id in &seq_ids {
+        let mut reported = Vec::new();
+        for entry in &coll.sequences[id].entries {
+            if !coll.entries.contains_key(entry) && !reported.contains(entry) {
+                reported.push(*entry);
+                problems.push(Problem::DanglingSeqEntry {
+                    seq: **id,
+                    entry: *entry,
+                });
+            }
+        }
+    }
+    for (tag, app) in &coll.tag_specific_apps {
+        if !coll.tags.contains_key(tag) {
+            problems.push(Problem::DanglingAppTag { tag: *tag });
+        } else if !db.preferences.applications.contains_key(app) {
+            problems.push(Problem::DanglingApp {
+                tag: *tag,
+                app: *app,
+            });
+        }
+    }
+    let mut by_path: FnvHashMap<&PathBuf, Vec<entry::Id>> = FnvHashMap::default();
+    for id in &entry_ids {
+        by_path
+            .entry(&coll.entries[id].path)
+            .or_default()
+            .push(**id);
+    }
+    let mut dup_paths: Vec<_> = by_path
+        .into_iter()
+        .filter(|(_, ids)| ids.len() > 1)
+        .collect();
+    dup_paths.sort_by_key(|(path, _)| *path);
+    for (path, ids) in dup_paths {
+        problems.push(Problem::DuplicatePath {
+            path: path.clone(),
+            ids,
+        });
+    }
+    let max_uid = entry_ids
+        .iter()
+        .map(|id| id.0)
+        .chain(tag_ids.iter().map(|id| id.0))
+        .chain(seq_ids.iter().map(|id| id.0))
+        .chain(db.collections.keys().map(|id| id.0))
+        .chain(db.preferences.applications.keys().map(|id| id.0))
+        .max();
+    if let Some(max) = max_uid
+        && max >= db.uid_counter.peek()
+    {
+        problems.push(Problem::UidsAboveCounter { max });
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{check, Problem},
+        crate::{
+            collection::Collection,
+            db::Db,
+            entry::{self, Entry},
+            preferences::AppId,
+            sequence::{self, Sequence},
+            tag::{self, Tag},
+        },
+    };
+
+    fn coll_with_two_entries(db: &mut Db) -> (Collection, entry::Id, entry::Id) {
+        let coll = Collection::make_new(&mut db.uid_counter, &["a.png", "b.png"]);
+        let mut ids: Vec<entry::Id> = coll.entries.keys().copied().collect();
+        ids.sort_by_key(|id| id.0);
+        (coll, ids[0], ids[1])
+    }
+
+    fn add_tag(coll: &mut Collection, db: &mut Db, names: &[&str]) -> tag::Id {
+        let id = tag::Id(db.uid_counter.next());
+        coll.tags.insert(
+            id,
+            Tag {
+                names: names.iter().map(ToString::to_string).collect(),
+                implies: Default::default(),
+            },
+        );
+        id
+    }
+
+    #[test]
+    fn consistent_collection_has_no_problems() {
+        let mut db = Db::default();
+        let (mut coll, a, b) = coll_with_two_entries(&mut db);
+        let tag = add_tag(&mut coll, &mut db, &["cat"]);
+        coll.add_tag_for(a, tag).unwrap();
+        coll.sequences.insert(
+            sequence::Id(db.uid_counter.next()),
+            Sequence {
+                name: "seq".into(),
+                entries: vec![a, b],
+            },
+        );
+        assert!(check(&coll, &db).is_empty());
+    }
+
+    #[test]
+    fn finds_and_repairs_problems() {
+        let mut db = Db::default();
+        let (mut coll, a, b) = coll_with_two_entries(&mut db);
+        let missing_tag = tag::Id(db.uid_counter.next());
+        let missing_entry = entry::Id(db.uid_counter.next());
+        let tag = add_tag(&mut coll, &mut db, &[]);
+        coll.add_tag_for(a, missing_tag).unwrap();
+        coll.tags.get_mut(&tag).unwrap().implies.insert(missing_tag);
+        coll.sequences.insert(
+            sequence::Id(db.uid_counter.next()),
+            Sequence {
+                name: "seq".into(),
+                entries: vec![a, missing_entry, missing_entry],
+            },
+        );
+        coll.tag_specific_apps
+            .insert(tag, AppId(db.uid_counter.next()));
+        coll.tag_specific_apps.insert(missing_tag, AppId(0));
+        coll.entries.get_mut(&b).unwrap().path = "a.png".into();
+        let dup = entry::Id(db.uid_counter.peek() + 10);
+        coll.entries.insert(dup, Entry::new("c.png".into()));
+
+        let problems = check(&coll, &db);
+        let count = |pred: fn(&Problem) -> bool| problems.iter().filter(|p| pred(p)).count();
+        assert_eq!(count(|p| matches!(p, Problem::DanglingEntryTag { .. })), 1);
+        assert_eq!(count(|p| matches!(p, Problem::DanglingImply { .. })), 1);
+        assert_eq!(count(|p| matches!(p, Problem::NamelessTag { .. })), 1);
+        // Reported once, no matter how many times the sequence contains it
+        assert_eq!(count(|p| matches!(p, Problem::DanglingSeqEntry { .. })), 1);
+        assert_eq!(count(|p| matches!(p, Problem::DanglingAppTag { .. })), 1);
+        assert_eq!(count(|p| matches!(p, Problem::DanglingApp { .. })), 1);
+        assert!(problems
+            .iter()
+            .any(|p| matches!(p, Problem::DuplicatePath { ids, .. } if ids == &[a, b])));
+        assert!(problems
+            .iter()
+            .any(|p| matches!(p, Problem::UidsAboveCounter { max } if *max == dup.0)));
+        assert_eq!(problems.len(), 8);
+
+        for problem in &problems {
+            problem.repair(&mut coll, &mut db).unwrap();
+        }
+        assert!(check(&coll, &db).is_empty());
+        assert!(!coll.entries.contains_key(&b));
+    }
+}
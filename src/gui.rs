@@ -1,7 +1,10 @@
+mod animation;
 pub mod debug_log;
+mod edit_watch;
 mod egui_ui;
 mod open;
 mod resources;
+mod thumbnail_cache;
 mod thumbnail_loader;
 mod thumbnails_view;
 mod util;
@@ -9,8 +12,11 @@ mod viewer;
 
 use {
     self::{
+        animation::HoverAnimation,
+        edit_watch::EditWatch,
         egui_ui::{Action, EguiState},
         resources::Resources,
+        thumbnail_cache::ThumbnailCache,
         thumbnail_loader::ThumbnailLoader,
         thumbnails_view::{
             handle_event, search_next, search_prev, select_all, SortBy, ThumbnailsView,
@@ -23,13 +29,13 @@ use {
         db::EntryMap,
         entry,
         filter_reqs::Requirements,
+        folder_watch::FolderWatcher,
         preferences::Preferences,
     },
     anyhow::Context as _,
     arboard::Clipboard,
     egui_sfml::{
         sfml::{
-            cpp::FBox,
             graphics::{
                 Color, Rect, RectangleShape, RenderTarget, RenderWindow, Shape, Text, Texture,
                 Transformable, View,
@@ -38,7 +44,6 @@ use {
         },
         SfEgui,
     },
-    rand::seq::SliceRandom,
     thumbnails_view::EventFlags,
 };
 
@@ -60,9 +65,9 @@ pub fn run(app: &mut Application) -> anyhow::Result<()> {
 
     if app.database.preferences.open_last_coll_at_start && !app.database.recent.is_empty() {
         match app.load_last() {
-            Ok(changes) => {
-                if !changes.empty() {
-                    egui_state.changes_window.open(changes);
+            Ok(scan) => {
+                if let Some(scan) = scan {
+                    egui_state.changes_window.start_scan(scan);
                 }
                 let coll = app
                     .active_collection
@@ -147,6 +152,13 @@ pub fn run(app: &mut Application) -> anyhow::Result<()> {
                 }
             }
         }
+        update_folder_watcher(&mut state, &mut egui_state, app);
+        if let Some((_, coll)) = &mut app.active_collection {
+            for id in state.edit_watch.poll(&coll.entries) {
+                state.invalidate_entry(id);
+            }
+            coll.store_phashes(state.thumbnail_loader.take_phashes());
+        }
         egui_state.begin_frame();
         let mut result = Ok(());
         let di = sf_egui.run(&mut window, |rw, ctx| {
@@ -205,7 +217,7 @@ pub fn run(app: &mut Application) -> anyhow::Result<()> {
                     }
                 }
                 Action::Shuffle => {
-                    state.thumbs_view.uids.shuffle(&mut rand::thread_rng());
+                    state.thumbs_view.shuffle();
                 }
                 Action::OpenEntriesWindow => {
                     let id_vec = state
@@ -238,6 +250,7 @@ pub fn run(app: &mut Application) -> anyhow::Result<()> {
                         &coll.entries,
                         load_anim_rotation,
                         !sf_egui.context().wants_pointer_input(),
+                        app.database.preferences.animate_thumbnails_on_hover,
                     );
                 }
                 Activity::Viewer => {
@@ -258,18 +271,12 @@ pub fn run(app: &mut Application) -> anyhow::Result<()> {
             }
         }
         if let Some(index) = state.thumbs_view.highlight {
-            let mut search_highlight = RectangleShape::with_size(
-                (
-                    state.thumbs_view.thumb_size as f32,
-                    state.thumbs_view.thumb_size as f32,
-                )
-                    .into(),
-            );
+            let mut rect = state.thumbs_view.item_rect(index);
+            rect.top -= state.thumbs_view.y_offset;
+            let mut search_highlight = RectangleShape::from_rect(rect);
             search_highlight.set_fill_color(Color::TRANSPARENT);
             search_highlight.set_outline_color(Color::RED);
             search_highlight.set_outline_thickness(-4.0);
-            let (x, y) = state.thumbs_view.item_position(index);
-            search_highlight.set_position((x as f32, y as f32 - state.thumbs_view.y_offset));
             window.draw(&search_highlight);
         }
         if let Some(tex) = egui_state.load_folder_window.texture.as_ref() {
@@ -291,8 +298,6 @@ pub fn run(app: &mut Application) -> anyhow::Result<()> {
     Ok(())
 }
 
-type ThumbnailCache = EntryMap<Option<FBox<Texture>>>;
-
 struct State {
     filter: Requirements,
     thumbnail_cache: ThumbnailCache,
@@ -308,6 +313,11 @@ struct State {
     select_a: Option<usize>,
     activity: Activity,
     viewer_state: ViewerState,
+    folder_watcher: Option<FolderWatcher>,
+    /// The collection the folder watcher was set up for
+    watched_coll: Option<collection::Id>,
+    hover_anim: Option<HoverAnimation>,
+    edit_watch: EditWatch,
 }
 pub struct SelectionBuf {
     pub buf: Vec<entry::Id>,
@@ -425,24 +435,77 @@ fn set_active_collection(
     std::env::set_current_dir(root).context("failed to set directory")
 }
 
+/// Keep the folder watcher in sync with the active collection, and handle the changes it finds
+fn update_folder_watcher(state: &mut State, egui_state: &mut EguiState, app: &mut Application) {
+    let want_watch = app
+        .active_collection
+        .as_ref()
+        .map(|(id, _)| *id)
+        .filter(|_| app.database.preferences.watch_folder);
+    if state.watched_coll != want_watch {
+        state.watched_coll = want_watch;
+        state.folder_watcher = None;
+        if let Some(id) = want_watch
+            && let Some(root) = app.database.collections.get(&id)
+        {
+            match FolderWatcher::new(root) {
+                Ok(watcher) => state.folder_watcher = Some(watcher),
+                Err(e) => egui_state
+                    .modal
+                    .err(format!("Failed to watch collection folder: {e}")),
+            }
+        }
+    }
+    let Some(watcher) = &mut state.folder_watcher else {
+        return;
+    };
+    let Some(touched) = watcher.poll() else {
+        return;
+    };
+    let Some((_, coll)) = &app.active_collection else {
+        return;
+    };
+    let root = watcher.root().to_owned();
+    let changes = match coll.changes_for_paths(&root, &touched, &mut watcher.file_ids) {
+        Ok(changes) => changes,
+        Err(e) => {
+            egui_state
+                .modal
+                .err(format!("Failed to check folder changes: {e:?}"));
+            return;
+        }
+    };
+    if changes.empty() {
+        return;
+    }
+    if coll.auto_apply_changes {
+        app.apply_changes_to_active_collection(&changes, |_, _| {});
+        if let Some((_, coll)) = &app.active_collection {
+            state
+                .thumbs_view
+                .update_from_collection(coll, &state.filter);
+        }
+    } else {
+        egui_state.changes_window.push(changes);
+    }
+}
+
 fn get_tex_for_entry<'t>(
     thumbnail_cache: &'t ThumbnailCache,
     id: entry::Id,
     entries: &Entries,
     thumbnail_loader: &ThumbnailLoader,
-    thumb_size: u32,
+    thumb_bounds: (u32, u32),
     res: &'t Resources,
 ) -> (bool, &'t Texture) {
     let (has_img, texture) = match thumbnail_cache.get(&id) {
-        Some(opt_texture) => match *opt_texture {
-            Some(ref tex) => (true, &**tex),
-            None => (false, &*res.error_texture),
-        },
+        Some(Ok(tex)) => (true, tex),
+        Some(Err(ft)) => (false, res.type_texture(ft)),
         None => {
             let Some(entry) = entries.get(&id) else {
                 return (false, &*res.error_texture);
             };
-            thumbnail_loader.request(&entry.path, thumb_size, id);
+            thumbnail_loader.request(&entry.path, thumb_bounds, id);
             (false, &*res.loading_texture)
         }
     };
@@ -453,8 +516,8 @@ impl State {
     fn new(window_width: u32, prefs: &Preferences) -> anyhow::Result<Self> {
         Ok(Self {
             filter: Requirements::default(),
-            thumbnail_cache: Default::default(),
-            thumbnail_loader: Default::default(),
+            thumbnail_cache: ThumbnailCache::new(prefs.thumbnail_cache_mib),
+            thumbnail_loader: ThumbnailLoader::new(prefs.thumbnail_threads.into()),
             search_cursor: 0,
             search_success: false,
             clipboard_ctx: Clipboard::new()?,
@@ -464,8 +527,55 @@ impl State {
             select_a: None,
             activity: Activity::Thumbnails,
             viewer_state: ViewerState::default(),
+            folder_watcher: None,
+            watched_coll: None,
+            hover_anim: None,
+            edit_watch: EditWatch::default(),
         })
     }
+    /// Replace the old entry ids with the new ones after compacting Uids.
+    ///
+    /// Ids not in `map` are forgotten. The filter has to be resolved again.
+    fn remap_entry_ids(&mut self, map: &EntryMap<entry::Id>) {
+        self.thumbnail_cache.remap_entry_ids(map);
+        self.sel.for_each_mut(|sel| {
+            sel.buf = sel
+                .buf
+                .iter()
+                .filter_map(|id| map.get(id).copied())
+                .collect();
+        });
+        self.select_a = None;
+        self.hover_anim = None;
+        self.edit_watch = EditWatch::default();
+        self.viewer_state.remap_entry_ids(map);
+        self.filter = Requirements::default();
+        self.find_reqs = Requirements::default();
+        self.wipe_search();
+    }
+    /// Drop the ids of entries that aren't in `entries` anymore from the selection buffers and
+    /// the viewer list, so nothing looks them up after they were deleted
+    fn forget_missing_entries(&mut self, entries: &Entries) {
+        self.sel
+            .for_each_mut(|sel| sel.buf.retain(|id| entries.contains_key(id)));
+        self.viewer_state
+            .retain_entries(|id| entries.contains_key(&id));
+        if self
+            .hover_anim
+            .as_ref()
+            .is_some_and(|anim| !entries.contains_key(&anim.id))
+        {
+            self.hover_anim = None;
+        }
+    }
+    /// Forget the cached images of `id`, so they get loaded again
+    fn invalidate_entry(&mut self, id: entry::Id) {
+        self.thumbnail_cache.remove(&id);
+        self.viewer_state.invalidate(id);
+        if self.hover_anim.as_ref().is_some_and(|anim| anim.id == id) {
+            self.hover_anim = None;
+        }
+    }
     fn wipe_search(&mut self) {
         self.search_cursor = 0;
         self.search_success = false;
@@ -0,0 +1,318 @@
+use {
+    super::thumbnail_loader::imagebuf_to_sf_tex,
+    crate::{archive, entry, exif_meta},
+    egui_sfml::sfml::{cpp::FBox, graphics::Texture},
+    image::{
+        codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder},
+        imageops::FilterType,
+        AnimationDecoder, DynamicImage, ImageFormat, ImageResult, RgbaImage,
+    },
+    parking_lot::Mutex,
+    std::{
+        io::Cursor,
+        path::Path,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant},
+    },
+};
+
+/// Decoded frames along with how long they should be shown
+pub type Frames = Vec<(RgbaImage, Duration)>;
+
+/// Browsers show frames with a delay below this for 100 ms, because many files rely on it
+const MIN_DELAY: Duration = Duration::from_millis(20);
+const FALLBACK_DELAY: Duration = Duration::from_millis(100);
+
+/// Decode all frames of an image. Still images decode to a single frame.
+pub fn decode(data: &[u8]) -> ImageResult<Frames> {
+    match decode_animated(data, |buf| buf, || false)? {
+        Some(frames) => Ok(frames),
+        None => decode_still(data),
+    }
+}
+
+/// Decode the frames of an animated image, passing each one through `map` as soon as it's
+/// decoded.
+///
+/// `None` if the image isn't animated. Once `cancelled` returns true, decoding stops and the
+/// frames so far are returned.
+fn decode_animated(
+    data: &[u8],
+    map: impl Fn(RgbaImage) -> RgbaImage,
+    cancelled: impl Fn() -> bool,
+) -> ImageResult<Option<Frames>> {
+    let frames = match image::guess_format(data)? {
+        ImageFormat::Gif => GifDecoder::new(Cursor::new(data))?.into_frames(),
+        ImageFormat::Png => {
+            if !png_has_actl(data) {
+                return Ok(None);
+            }
+            PngDecoder::new(Cursor::new(data))?.apng()?.into_frames()
+        }
+        ImageFormat::WebP => {
+            let dec = WebPDecoder::new(Cursor::new(data))?;
+            if !dec.has_animation() {
+                return Ok(None);
+            }
+            dec.into_frames()
+        }
+        _ => return Ok(None),
+    };
+    let mut decoded = Frames::new();
+    for frame in frames {
+        if cancelled() {
+            break;
+        }
+        let frame = frame?;
+        let mut delay = Duration::from(frame.delay());
+        if delay < MIN_DELAY {
+            delay = FALLBACK_DELAY;
+        }
+        decoded.push((map(frame.into_buffer()), delay));
+    }
+    // Single frame GIFs are still images too
+    Ok((decoded.len() > 1).then_some(decoded))
+}
+
+/// Whether the PNG in `data` has an animation control (`acTL`) chunk, making it an APNG.
+///
+/// The chunk has to come before the image data, so only the chunks up to that are looked at.
+fn png_has_actl(data: &[u8]) -> bool {
+    // Skip the signature
+    let mut pos = 8;
+    while let Some(header) = data.get(pos..pos + 8) {
+        match &header[4..] {
+            b"acTL" => return true,
+            b"IDAT" => return false,
+            _ => {}
+        }
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        // Length, type, data and CRC
+        pos = pos.saturating_add(len as usize).saturating_add(12);
+    }
+    false
+}
+
+fn decode_still(data: &[u8]) -> ImageResult<Frames> {
+    let img = exif_meta::load_oriented(data)?;
+    Ok(vec![(img.to_rgba8(), Duration::ZERO)])
+}
+
+/// Whether files with this path could be animated, judging by the extension
+pub fn may_be_animated(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            ["gif", "png", "apng", "webp"]
+                .iter()
+                .any(|anim_ext| ext.eq_ignore_ascii_case(anim_ext))
+        })
+}
+
+/// Frames uploaded as textures, along with the playback position
+pub struct Animation {
+    frames: Vec<(FBox<Texture>, Duration)>,
+    current: usize,
+    /// How long the current frame has been shown, at normal speed
+    shown_for: Duration,
+    last_update: Instant,
+}
+
+impl Animation {
+    pub fn new(frames: Frames) -> Self {
+        Self {
+            frames: frames
+                .into_iter()
+                .map(|(buf, delay)| (imagebuf_to_sf_tex(buf), delay))
+                .collect(),
+            current: 0,
+            shown_for: Duration::ZERO,
+            last_update: Instant::now(),
+        }
+    }
+    pub fn is_animated(&self) -> bool {
+        self.frames.len() > 1
+    }
+    pub fn n_frames(&self) -> usize {
+        self.frames.len()
+    }
+    /// Bytes taken up by the textures of all frames
+    pub fn n_bytes(&self) -> usize {
+        self.frames
+            .iter()
+            .map(|(tex, _)| {
+                let size = tex.size();
+                size.x as usize * size.y as usize * 4
+            })
+            .sum()
+    }
+    pub fn current_frame(&self) -> usize {
+        self.current
+    }
+    pub fn tex(&self) -> &Texture {
+        &self.frames[self.current].0
+    }
+    /// Advance the playback by the time passed since the last update, multiplied by `speed`
+    pub fn update(&mut self, paused: bool, speed: f32) {
+        let elapsed = self.last_update.elapsed();
+        self.last_update = Instant::now();
+        if paused || !self.is_animated() {
+            return;
+        }
+        self.shown_for += elapsed.mul_f32(speed.max(0.0));
+        // Don't spin through a lot of frames after a long stall, like a window drag
+        let mut skipped = 0;
+        while self.shown_for >= self.frames[self.current].1 && skipped < self.frames.len() {
+            self.shown_for -= self.frames[self.current].1;
+            self.current = (self.current + 1) % self.frames.len();
+            skipped += 1;
+        }
+        if skipped == self.frames.len() {
+            self.shown_for = Duration::ZERO;
+        }
+    }
+    pub fn step(&mut self, forward: bool) {
+        let len = self.frames.len();
+        self.current = if forward {
+            (self.current + 1) % len
+        } else {
+            (self.current + len - 1) % len
+        };
+        self.shown_for = Duration::ZERO;
+    }
+}
+
+/// Animation of the thumbnail under the mouse cursor, decoded in the background
+pub struct HoverAnimation {
+    pub id: entry::Id,
+    /// The frames, or `None` if the image turned out not to be animated
+    decoded: Arc<Mutex<Option<ImageResult<Option<Frames>>>>>,
+    /// Set when dropped, so the decoding stops once the cursor moves on
+    cancelled: Arc<AtomicBool>,
+    anim: Option<Animation>,
+}
+
+impl HoverAnimation {
+    /// Start decoding `path` scaled down to fit `bounds`, if it could be animated
+    pub fn start(id: entry::Id, path: &Path, (width, height): (u32, u32)) -> Option<Self> {
+        if !may_be_animated(path) {
+            return None;
+        }
+        let decoded = Arc::new(Mutex::new(None));
+        let decoded_clone = Arc::clone(&decoded);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let cancelled_clone = Arc::clone(&cancelled);
+        let path = path.to_owned();
+        std::thread::spawn(move || {
+            let is_cancelled = || cancelled_clone.load(Ordering::Relaxed);
+            let result = archive::read(&path)
+                .map_err(image::ImageError::IoError)
+                .and_then(|data| {
+                    if is_cancelled() {
+                        return Ok(None);
+                    }
+                    // Only the scaled down frames are kept around
+                    let scale = |buf| {
+                        DynamicImage::ImageRgba8(buf)
+                            .resize(width, height, FilterType::Triangle)
+                            .to_rgba8()
+                    };
+                    decode_animated(&data, scale, is_cancelled)
+                });
+            *decoded_clone.lock() = Some(result);
+        });
+        Some(Self {
+            id,
+            decoded,
+            cancelled,
+            anim: None,
+        })
+    }
+    /// Pick up the decoded frames when they are ready, and advance the playback
+    pub fn update(&mut self) {
+        if self.anim.is_none()
+            && let Some(Ok(Some(frames))) = self.decoded.lock().take()
+        {
+            self.anim = Some(Animation::new(frames));
+        }
+        if let Some(anim) = &mut self.anim {
+            anim.update(false, 1.0);
+        }
+    }
+    /// The current frame, if decoding is done and there is more than one frame
+    pub fn frame(&self) -> Option<&Texture> {
+        self.anim
+            .as_ref()
+            .filter(|anim| anim.is_animated())
+            .map(Animation::tex)
+    }
+}
+
+impl Drop for HoverAnimation {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::png_has_actl;
+
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1A\n";
+
+    /// A PNG signature followed by chunks of the given types and data lengths, with dummy CRCs
+    fn png(chunks: &[(&[u8; 4], usize)]) -> Vec<u8> {
+        let mut data = SIGNATURE.to_vec();
+        for (ty, len) in chunks {
+            data.extend_from_slice(&(*len as u32).to_be_bytes());
+            data.extend_from_slice(*ty);
+            data.resize(data.len() + len + 4, 0);
+        }
+        data
+    }
+
+    #[test]
+    fn actl_before_image_data() {
+        assert!(png_has_actl(&png(&[
+            (b"IHDR", 13),
+            (b"acTL", 8),
+            (b"fcTL", 26),
+            (b"IDAT", 100),
+            (b"IEND", 0)
+        ])));
+    }
+
+    #[test]
+    fn no_actl() {
+        assert!(!png_has_actl(&png(&[
+            (b"IHDR", 13),
+            (b"IDAT", 100),
+            (b"IEND", 0)
+        ])));
+        assert!(!png_has_actl(SIGNATURE));
+        assert!(!png_has_actl(b""));
+    }
+
+    #[test]
+    fn actl_after_image_data_is_ignored() {
+        assert!(!png_has_actl(&png(&[
+            (b"IHDR", 13),
+            (b"IDAT", 100),
+            (b"acTL", 8),
+            (b"IEND", 0)
+        ])));
+    }
+
+    #[test]
+    fn truncated_or_bogus_lengths() {
+        let mut data = png(&[(b"IHDR", 13), (b"acTL", 8)]);
+        data.truncate(SIGNATURE.len() + 8 + 13 + 4 + 6);
+        assert!(!png_has_actl(&data));
+        let mut data = png(&[(b"IHDR", 13)]);
+        data[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(!png_has_actl(&data));
+    }
+}
@@ -0,0 +1,57 @@
+//! Noticing when entry files change on disk, so their cached images can be loaded again
+
+use {
+    crate::{archive, collection::Entries, db::EntryMap, entry},
+    std::{
+        path::{Path, PathBuf},
+        time::{Duration, Instant, SystemTime},
+    },
+};
+
+/// How often files are checked for changes
+pub const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Entries opened in an external editor, checked for writes while the session runs
+pub struct EditWatch {
+    /// Path and modification time when last checked
+    watched: EntryMap<(PathBuf, Option<SystemTime>)>,
+    last_check: Instant,
+}
+
+impl Default for EditWatch {
+    fn default() -> Self {
+        Self {
+            watched: EntryMap::default(),
+            last_check: Instant::now(),
+        }
+    }
+}
+
+impl EditWatch {
+    pub fn watch(&mut self, id: entry::Id, path: &Path) {
+        self.watched
+            .insert(id, (path.to_owned(), archive::mtime(path)));
+    }
+    /// Returns the watched entries that were written to since the last check.
+    ///
+    /// These stop being watched, just like the ones no longer in `entries`. Once loaded again,
+    /// further changes are picked up by the caches themselves.
+    pub fn poll(&mut self, entries: &Entries) -> Vec<entry::Id> {
+        if self.last_check.elapsed() < CHECK_INTERVAL {
+            return Vec::new();
+        }
+        self.last_check = Instant::now();
+        let mut changed = Vec::new();
+        self.watched.retain(|id, (path, last_mtime)| {
+            if !entries.contains_key(id) {
+                return false;
+            }
+            if archive::mtime(path) != *last_mtime {
+                changed.push(*id);
+                return false;
+            }
+            true
+        });
+        changed
+    }
+}
@@ -3,15 +3,25 @@ mod changes_window;
 mod coll_prefs_window;
 mod collections_window;
 mod debug_window;
+mod duplicates_window;
 mod entries_window;
+mod explain_window;
+mod extract_window;
 mod filter_popup;
 mod find_popup;
+mod fsck_window;
 mod icons;
+mod import_window;
 mod load_folder_window;
+mod merge_window;
 mod modal;
+mod move_rules_window;
 mod preferences_window;
+mod query_builder_window;
 mod query_popup;
+mod saved_searches_window;
 mod sequences;
+mod similar_window;
 mod tag_autocomplete;
 mod tag_list;
 mod top_bar;
@@ -24,18 +34,28 @@ use {
         coll_prefs_window::CollPrefsWindow,
         collections_window::CollectionsDbWindow,
         debug_window::DebugWindow,
+        duplicates_window::DuplicatesWindow,
         entries_window::EntriesWindow,
+        explain_window::ExplainWindow,
+        extract_window::ExtractWindow,
+        fsck_window::FsckWindow,
+        import_window::ImportWindow,
         load_folder_window::LoadFolderWindow,
+        merge_window::MergeWindow,
+        move_rules_window::MoveRulesWindow,
         preferences_window::PreferencesWindow,
+        query_builder_window::QueryBuilderWindow,
         query_popup::QueryPopup,
+        saved_searches_window::SavedSearchesWindow,
         sequences::{SequenceWindow, SequencesWindow},
+        similar_window::SimilarWindow,
         tag_list::TagWindow,
     },
     super::{get_tex_for_entry, resources::Resources},
     crate::{
         application::Application,
         collection::{Collection, TagsExt},
-        entry,
+        dlog, entry,
         gui::State,
         preferences::Preferences,
     },
@@ -73,6 +93,16 @@ pub(crate) struct EguiState {
     pub(crate) coll_prefs_window: CollPrefsWindow,
     pub(crate) batch_rename_window: BatchRenameWindow,
     pub(crate) collections_db_window: CollectionsDbWindow,
+    pub(crate) saved_searches_window: SavedSearchesWindow,
+    pub(crate) explain_window: ExplainWindow,
+    pub(crate) query_builder_window: QueryBuilderWindow,
+    pub(crate) duplicates_window: DuplicatesWindow,
+    pub(crate) similar_window: SimilarWindow,
+    pub(crate) move_rules_window: MoveRulesWindow,
+    pub(crate) import_window: ImportWindow,
+    pub(crate) merge_window: MergeWindow,
+    pub(crate) extract_window: ExtractWindow,
+    pub(crate) fsck_window: FsckWindow,
     pub(crate) file_dialog: FileDialog,
     /// If `Some`, save this screenshot to the selected path of the file dialog
     pub(crate) file_op: Option<FileOp>,
@@ -85,6 +115,10 @@ pub(crate) enum FileOp {
     SaveScreenshot(FBox<Image>),
     CreateBackup,
     RestoreBackup,
+    /// Add the selected path to the sources of the import window
+    ImportSource,
+    /// Use the selected folder as the root of the collection to extract into
+    ExtractRoot,
 }
 
 impl EguiState {
@@ -107,6 +141,16 @@ impl EguiState {
             coll_prefs_window: Default::default(),
             batch_rename_window: Default::default(),
             collections_db_window: Default::default(),
+            saved_searches_window: Default::default(),
+            explain_window: Default::default(),
+            query_builder_window: Default::default(),
+            duplicates_window: Default::default(),
+            similar_window: Default::default(),
+            move_rules_window: Default::default(),
+            import_window: Default::default(),
+            merge_window: Default::default(),
+            extract_window: Default::default(),
+            fsck_window: Default::default(),
             file_dialog: FileDialog::new()
                 .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::default()),
             file_op: None,
@@ -178,6 +222,7 @@ pub(super) fn do_ui(
                     .modal
                     .success(format!("Successful merge into {into_name}"));
             }
+            PromptAction::CompactUids => compact_uids(state, egui_state, app, win),
             PromptAction::PanicTest => panic!("User inflicted panic"),
         }
     }
@@ -185,9 +230,12 @@ pub(super) fn do_ui(
     preferences_window::do_frame(state, egui_state, app, egui_ctx, win);
     load_folder_window::do_frame(state, egui_state, egui_ctx, res, app, win.size().x);
     changes_window::do_frame(state, egui_state, egui_ctx, app, win);
-    debug_window::do_frame(egui_state, egui_ctx);
+    debug_window::do_frame(state, egui_state, egui_ctx);
     collections_window::do_frame(app, egui_state, egui_ctx);
-    if let Some((_id, coll)) = app.active_collection.as_mut() {
+    merge_window::do_frame(state, egui_state, app, egui_ctx, win);
+    extract_window::do_frame(state, egui_state, app, egui_ctx);
+    fsck_window::do_frame(state, egui_state, app, egui_ctx, win);
+    if let Some((coll_id, coll)) = app.active_collection.as_mut() {
         find_popup::do_frame(state, egui_state, egui_ctx, coll, win);
         if filter_popup::do_frame(state, egui_state, egui_ctx, coll) {
             state
@@ -220,6 +268,13 @@ pub(super) fn do_ui(
             win,
         );
         coll_prefs_window::do_frame(egui_state, coll, egui_ctx, &app.database.preferences);
+        saved_searches_window::do_frame(state, egui_state, coll, egui_ctx, win);
+        explain_window::do_frame(state, egui_state, coll, egui_ctx);
+        query_builder_window::do_frame(state, egui_state, coll, egui_ctx, win);
+        if let Some(root) = app.database.collections.get(coll_id) {
+            duplicates_window::do_frame(state, egui_state, coll, *coll_id, root, egui_ctx, win);
+            similar_window::do_frame(state, egui_state, coll, *coll_id, root, egui_ctx, win);
+        }
         entries_window::do_frame(
             state,
             egui_state,
@@ -230,6 +285,15 @@ pub(super) fn do_ui(
             res,
         );
         batch_rename_window::do_frame(state, egui_state, coll, egui_ctx, win);
+        move_rules_window::do_frame(state, egui_state, coll, egui_ctx, win);
+        import_window::do_frame(
+            state,
+            egui_state,
+            coll,
+            &mut app.database.uid_counter,
+            egui_ctx,
+            win,
+        );
     }
     if let Some(op) = &egui_state.file_op
         && let Some(path) = egui_state.file_dialog.take_selected()
@@ -237,10 +301,8 @@ pub(super) fn do_ui(
         match op {
             FileOp::OpenDirectory => {
                 if let Some(id) = app.database.find_collection_by_path(&path) {
-                    let changes = app.load_collection(id)?;
-                    if !changes.empty() {
-                        egui_state.changes_window.open(changes);
-                    }
+                    let scan = app.load_collection(id)?;
+                    egui_state.changes_window.start_scan(scan);
                     let result = crate::gui::set_active_collection(
                         &mut state.thumbs_view,
                         app,
@@ -288,6 +350,12 @@ pub(super) fn do_ui(
                     egui_state.modal.success("Backup restored");
                 }
             }
+            FileOp::ImportSource => {
+                egui_state.import_window.sources.push(path);
+            }
+            FileOp::ExtractRoot => {
+                egui_state.extract_window.new_root = path.to_string_lossy().into_owned();
+            }
         }
         egui_state.file_op = None;
     }
@@ -295,7 +363,87 @@ pub(super) fn do_ui(
     Ok(())
 }
 
+/// Compact the Uids, and remap or forget everything in the ui that refers to the old ones
+fn compact_uids(
+    state: &mut State,
+    egui_state: &mut EguiState,
+    app: &mut Application,
+    win: &RenderWindow,
+) {
+    if !state.thumbnail_loader.is_idle() {
+        egui_state
+            .modal
+            .err("Thumbnails are still loading, try again when they are done");
+        return;
+    }
+    // These are keyed by the old ids, they can be computed again
+    let _ = state.thumbnail_loader.take_phashes();
+    let had_active = app.active_collection.is_some();
+    let result = app.compact_uids();
+    let (map, scan) = match result {
+        Ok(Some((map, scan))) => (map, Some(scan)),
+        Ok(None) => (Default::default(), None),
+        Err(e) => {
+            // The active collection is closed once the ids are changed
+            if had_active && app.active_collection.is_none() {
+                egui_state.forget_ids();
+                state.remap_entry_ids(&Default::default());
+            }
+            egui_state
+                .modal
+                .err(format!("Failed to compact Uids: {e:?}"));
+            return;
+        }
+    };
+    egui_state.forget_ids();
+    state.remap_entry_ids(&map.entries);
+    for popup in [&mut egui_state.filter_popup, &mut egui_state.find_popup] {
+        popup.string = filter_reqs::remap_similar_ids(&popup.string, &map.entries);
+    }
+    if let Some(scan) = scan {
+        egui_state.changes_window.start_scan(scan);
+    }
+    if let Some((id, coll)) = &app.active_collection {
+        if let Err(e) = state
+            .filter
+            .parse_and_resolve(&egui_state.filter_popup.string, coll)
+        {
+            dlog!("Failed to resolve filter after compaction: {e}");
+            egui_state.filter_popup.string.clear();
+            state.filter = Default::default();
+        }
+        let result = crate::gui::set_active_collection(
+            &mut state.thumbs_view,
+            app,
+            *id,
+            &state.filter,
+            win.size().x,
+        );
+        if let Err(e) = result {
+            egui_state
+                .modal
+                .err(format!("Failed to set active collection: {e:?}"));
+            return;
+        }
+    }
+    egui_state.modal.success("Uids compacted");
+}
+
 impl EguiState {
+    /// Reset the windows that refer to entries, tags or sequences by their ids
+    fn forget_ids(&mut self) {
+        self.entries_windows.clear();
+        self.sequence_windows.clear();
+        self.sequences_window = Default::default();
+        self.tag_window = Default::default();
+        self.batch_rename_window = Default::default();
+        self.explain_window = Default::default();
+        self.duplicates_window = Default::default();
+        self.similar_window = Default::default();
+        self.move_rules_window = Default::default();
+        self.merge_window = Default::default();
+        self.fsck_window = Default::default();
+    }
     pub(crate) fn add_entries_window(&mut self, vec: Vec<entry::Id>) {
         self.entries_windows
             .push(EntriesWindow::new(vec, self.egui_uid_counter));
@@ -356,7 +504,7 @@ impl egui_sfml::UserTexSource for TexSrc<'_, '_, '_> {
                     entry::Id(id),
                     &coll.entries,
                     &self.state.thumbnail_loader,
-                    self.state.thumbs_view.thumb_size,
+                    self.state.thumbs_view.thumb_bounds(),
                     self.res,
                 )
                 .1
@@ -1,17 +1,18 @@
 use {
     super::EguiState,
     crate::{
-        application::Application, db::FolderChanges, entry, gui::thumbnails_view::ThumbnailsView,
+        application::Application, archive, db::FolderChanges, entry, folder_scan::ChangesScan,
+        gui::thumbnails_view::ThumbnailsView,
     },
     egui_sfml::{
         egui::{
-            self, load::SizedTexture, Color32, Context, ImageButton, Label, RichText, ScrollArea,
-            TextureId, Window,
+            self, load::SizedTexture, Align2, Color32, Context, ImageButton, Label, RichText,
+            ScrollArea, TextureId, Window,
         },
         sfml::graphics::{RenderTarget, RenderWindow},
     },
     fnv::FnvHashMap,
-    std::path::PathBuf,
+    std::{path::PathBuf, sync::atomic::Ordering},
 };
 
 struct AddedInfo {
@@ -25,6 +26,8 @@ pub struct ChangesWindow {
     changes: FolderChanges,
     added: FnvHashMap<PathBuf, AddedInfo>,
     applied: bool,
+    /// Folder scan running in the background, the window opens with its results
+    scan: Option<ChangesScan>,
 }
 
 enum Action {
@@ -38,6 +41,44 @@ pub(super) fn do_frame(
     app: &mut Application,
     rw: &RenderWindow,
 ) {
+    let win = &mut egui_state.changes_window;
+    if let Some(scan) = &mut win.scan {
+        match scan.poll() {
+            None => {
+                let n_files = scan.progress.files_walked.load(Ordering::Relaxed);
+                Window::new("Scanning folder")
+                    .anchor(Align2::RIGHT_BOTTOM, [-16.0, -16.0])
+                    .title_bar(false)
+                    .auto_sized()
+                    .show(egui_ctx, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label(format!("Scanning folder for changes... {n_files} files"));
+                        });
+                    });
+            }
+            Some(result) => {
+                let coll_id = scan.coll_id;
+                win.scan = None;
+                match result {
+                    Ok(mut changes) => {
+                        // Only relevant if the same collection is still active
+                        if let Some((id, coll)) = &mut app.active_collection
+                            && *id == coll_id
+                        {
+                            for id in changes.stale_phashes.drain(..) {
+                                coll.perceptual_hashes.remove(&id);
+                            }
+                            if !changes.empty() {
+                                win.open(changes);
+                            }
+                        }
+                    }
+                    Err(e) => egui_state.modal.err(format!("Folder scan error: {e:?}")),
+                }
+            }
+        }
+    }
     let win = &mut egui_state.changes_window;
     if !win.open {
         return;
@@ -105,6 +146,20 @@ pub(super) fn do_frame(
                         });
                     });
                 }
+                if !changes.rename.is_empty() {
+                    ui.vertical(|ui| {
+                        ui.set_height(600.);
+                        ui.set_width(400.);
+                        ui.heading("Renamed");
+                        ScrollArea::vertical()
+                            .id_salt("scroll_rename")
+                            .show(ui, |ui| {
+                                for (from, to) in &changes.rename {
+                                    ui.label(format!("{} → {}", from.display(), to.display()));
+                                }
+                            });
+                    });
+                }
                 if !changes.remove.is_empty() {
                     ui.vertical(|ui| {
                         ui.set_height(600.);
@@ -175,4 +230,46 @@ impl ChangesWindow {
         self.open = true;
         self.changes = changes;
     }
+    pub(crate) fn start_scan(&mut self, scan: ChangesScan) {
+        self.scan = Some(scan);
+    }
+    /// Add changes found while running, merging them with the ones not yet applied
+    pub(crate) fn push(&mut self, changes: FolderChanges) {
+        if self.applied || !self.open {
+            self.changes = FolderChanges::default();
+            self.added.clear();
+            self.applied = false;
+        }
+        self.open = true;
+        // Earlier changes might have been undone since
+        // Archives in the removals were replaced by their members, they're meant to exist
+        self.changes
+            .add
+            .retain(|path| archive::on_disk_path(path).exists());
+        self.changes
+            .remove
+            .retain(|path| !path.exists() || archive::is_expandable(path));
+        for path in changes.add {
+            if !self.changes.add.contains(&path) {
+                self.changes.add.push(path);
+            }
+        }
+        for path in changes.remove {
+            if !self.changes.remove.contains(&path) {
+                self.changes.remove.push(path);
+            }
+        }
+        for (from, to) in changes.rename {
+            // A path renamed again before applying only needs its latest name
+            match self
+                .changes
+                .rename
+                .iter_mut()
+                .find(|(_, pending_to)| *pending_to == from)
+            {
+                Some((_, pending_to)) => *pending_to = to,
+                None => self.changes.rename.push((from, to)),
+            }
+        }
+    }
 }
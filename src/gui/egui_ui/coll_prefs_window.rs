@@ -1,7 +1,8 @@
 use {
     super::EguiState,
     crate::{
-        collection::{Collection, TagsExt},
+        collection::{ArchivePolicy, Collection, LinkPolicy, TagsExt},
+        folder_scan::{DEFAULT_IGNORE_PATTERNS, IGNORE_FILENAME},
         preferences::{AppMapExt, Preferences},
     },
     egui_sfml::egui,
@@ -24,6 +25,7 @@ struct AddBuf {
 enum Tab {
     #[default]
     IgnoredExts,
+    IgnorePatterns,
     TagSpecificApps,
 }
 
@@ -44,6 +46,12 @@ pub(super) fn do_frame(
                 {
                     win.tab = Tab::IgnoredExts;
                 }
+                if ui
+                    .selectable_label(win.tab == Tab::IgnorePatterns, "Ignore patterns")
+                    .clicked()
+                {
+                    win.tab = Tab::IgnorePatterns;
+                }
                 if ui
                     .selectable_label(win.tab == Tab::TagSpecificApps, "Tag specific apps")
                     .clicked()
@@ -52,6 +60,14 @@ pub(super) fn do_frame(
                 }
             });
             ui.separator();
+            ui.checkbox(
+                &mut coll.auto_apply_changes,
+                "Apply folder changes automatically (trusted folder)",
+            )
+            .on_hover_text("Files added to or removed from the folder are applied without asking");
+            link_policy_ui(ui, &mut coll.link_policy);
+            archive_policy_ui(ui, &mut coll.archive_policy);
+            ui.separator();
             match win.tab {
                 Tab::IgnoredExts => {
                     coll.ignored_extensions.retain_mut(|ext| {
@@ -66,6 +82,36 @@ pub(super) fn do_frame(
                         coll.ignored_extensions.push(String::new());
                     }
                 }
+                Tab::IgnorePatterns => {
+                    ui.label("Gitignore-style patterns, relative to the collection folder")
+                        .on_hover_text(format!(
+                            "{IGNORE_FILENAME} files in the folder and its subfolders are also \
+                             honored.\nPut one containing `*` into a folder to skip it while \
+                             it's a work in progress."
+                        ));
+                    coll.ignore_patterns.retain_mut(|pat| {
+                        let mut retain = true;
+                        ui.horizontal(|ui| {
+                            ui.text_edit_singleline(pat);
+                            if ui.button("-").clicked() {
+                                retain = false;
+                            }
+                        });
+                        retain
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.button("Add new").clicked() {
+                            coll.ignore_patterns.push(String::new());
+                        }
+                        if ui.button("Add defaults").clicked() {
+                            for pat in DEFAULT_IGNORE_PATTERNS {
+                                if !coll.ignore_patterns.iter().any(|p| p == pat) {
+                                    coll.ignore_patterns.push(pat.to_string());
+                                }
+                            }
+                        }
+                    });
+                }
                 Tab::TagSpecificApps => {
                     ui.heading("Tag specific applications");
                     ui.label("new");
@@ -91,3 +137,29 @@ pub(super) fn do_frame(
             }
         });
 }
+
+pub(super) fn link_policy_ui(ui: &mut egui::Ui, policy: &mut LinkPolicy) {
+    ui.checkbox(&mut policy.follow_symlinks, "Follow symlinked folders")
+        .on_hover_text("Symlink loops are detected and skipped");
+    ui.checkbox(
+        &mut policy.dedup_inodes,
+        "Treat links to the same file as one entry",
+    )
+    .on_hover_text("Hardlinked files and symlinks to files only get added once");
+}
+
+fn archive_policy_ui(ui: &mut egui::Ui, policy: &mut ArchivePolicy) {
+    ui.checkbox(&mut policy.expand, "Browse images inside ZIP/CBZ archives")
+        .on_hover_text(
+            "Each image in an archive becomes an entry, instead of the archive itself.\n\
+             Turning this off only affects new archives. \
+             Archives that already have image entries stay expanded, to keep their tags.",
+        );
+    ui.add_enabled_ui(policy.expand, |ui| {
+        ui.checkbox(
+            &mut policy.make_sequences,
+            "Make a sequence out of each archive",
+        )
+        .on_hover_text("Named after the archive, in the order of the images inside it");
+    });
+}
@@ -1,5 +1,6 @@
 use {
     super::EguiState,
+    crate::gui::State,
     egui_sfml::egui::{self, Align, Color32, Context, Label, RichText, ScrollArea, Window},
 };
 
@@ -25,7 +26,7 @@ impl DebugWindow {
     }
 }
 
-pub(super) fn do_frame(egui_state: &mut EguiState, egui_ctx: &Context) {
+pub(super) fn do_frame(state: &State, egui_state: &mut EguiState, egui_ctx: &Context) {
     let win = &mut egui_state.debug_window;
     if !win.open {
         return;
@@ -33,6 +34,14 @@ pub(super) fn do_frame(egui_state: &mut EguiState, egui_ctx: &Context) {
     Window::new("Debug window")
         .open(&mut win.open)
         .show(egui_ctx, |ui| {
+            let cache = &state.thumbnail_cache;
+            ui.label(format!(
+                "Thumbnail cache: {} thumbnails, {:.1} / {:.1} MiB",
+                cache.n_slots(),
+                bytes_to_mib(cache.used_bytes()),
+                bytes_to_mib(cache.budget_bytes())
+            ));
+            ui.separator();
             ui.heading("Debug log");
             let log = &crate::gui::debug_log::LOG;
             ui.group(|ui| {
@@ -71,3 +80,7 @@ pub(super) fn do_frame(egui_state: &mut EguiState, egui_ctx: &Context) {
             });
         });
 }
+
+fn bytes_to_mib(bytes: usize) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
@@ -0,0 +1,287 @@
+use {
+    super::EguiState,
+    crate::{
+        archive,
+        collection::{self, Collection},
+        dup_finder::DupScan,
+        entry,
+        folder_scan::{file_id, literal_ignore_pattern, FileId},
+        gui::State,
+    },
+    egui_sfml::{
+        egui::{self, load::SizedTexture, Color32, ImageButton, RichText, ScrollArea, TextureId},
+        sfml::graphics::RenderWindow,
+    },
+    std::{path::Path, sync::atomic::Ordering},
+};
+
+/// Finds entries with identical file contents, and merges them into one
+#[derive(Default)]
+pub struct DuplicatesWindow {
+    pub open: bool,
+    scan: Option<DupScan>,
+    groups: Vec<DupGroup>,
+    /// Whether a search finished, to tell "no duplicates" apart from "not searched yet"
+    searched: bool,
+}
+
+struct DupGroup {
+    ids: Vec<entry::Id>,
+    /// File ids of `ids`, to tell links to the same file apart from copies
+    file_ids: Vec<Option<FileId>>,
+    /// Index into `ids` of the entry to keep
+    keep: usize,
+    /// Asking for confirmation before deleting the other files
+    delete_confirm: bool,
+}
+
+impl DupGroup {
+    /// Whether the entry at `idx` is the same file as the kept one, through a link
+    fn is_same_file_as_kept(&self, idx: usize) -> bool {
+        idx != self.keep
+            && self.file_ids[idx].is_some()
+            && self.file_ids[idx] == self.file_ids[self.keep]
+    }
+    /// Number of files deleting the others would delete
+    fn n_files_to_delete(&self) -> usize {
+        (0..self.ids.len())
+            .filter(|&i| i != self.keep && !self.is_same_file_as_kept(i))
+            .count()
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Resolution {
+    /// Delete the other files
+    Delete,
+    /// Remove the other entries and add them to the ignore patterns
+    Exclude,
+}
+
+enum Action {
+    Search,
+    Resolve { group: usize, how: Resolution },
+}
+
+pub(super) fn do_frame(
+    state: &mut State,
+    egui_state: &mut EguiState,
+    coll: &mut Collection,
+    coll_id: collection::Id,
+    root: &Path,
+    egui_ctx: &egui::Context,
+    rw: &RenderWindow,
+) {
+    let win = &mut egui_state.duplicates_window;
+    if let Some(scan) = &mut win.scan
+        && let Some(result) = scan.poll()
+    {
+        let scan_coll = scan.coll_id;
+        win.scan = None;
+        match result {
+            Ok(groups) if scan_coll == coll_id => {
+                win.groups = groups
+                    .into_iter()
+                    .map(|ids| DupGroup {
+                        file_ids: ids
+                            .iter()
+                            .map(|id| file_id(&root.join(&coll.entries[id].path)))
+                            .collect(),
+                        ids,
+                        keep: 0,
+                        delete_confirm: false,
+                    })
+                    .collect();
+                win.searched = true;
+            }
+            Ok(_) => {}
+            Err(e) => egui_state
+                .modal
+                .err(format!("Duplicate search error: {e:?}")),
+        }
+    }
+    let win = &mut egui_state.duplicates_window;
+    if !win.open {
+        return;
+    }
+    // Entries could have gone away since the search
+    for group in &mut win.groups {
+        let mut i = 0;
+        while i < group.ids.len() {
+            if coll.entries.contains_key(&group.ids[i]) {
+                i += 1;
+            } else {
+                group.ids.remove(i);
+                group.file_ids.remove(i);
+            }
+        }
+        group.keep = group.keep.min(group.ids.len().saturating_sub(1));
+    }
+    win.groups.retain(|group| group.ids.len() > 1);
+    let mut action = None;
+    egui::Window::new("Duplicates")
+        .open(&mut win.open)
+        .show(egui_ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(win.scan.is_none(), egui::Button::new("Search"))
+                    .on_hover_text("Hash the contents of every file in the collection")
+                    .clicked()
+                {
+                    action = Some(Action::Search);
+                }
+                if let Some(scan) = &win.scan {
+                    ui.spinner();
+                    ui.label(format!(
+                        "Hashing {}/{}",
+                        scan.progress.hashed.load(Ordering::Relaxed),
+                        scan.progress.to_hash.load(Ordering::Relaxed)
+                    ));
+                } else if win.searched {
+                    ui.label(format!("{} groups of identical files", win.groups.len()));
+                }
+            });
+            ui.label("Click an image to choose the copy to keep");
+            ui.label("The kept copy gets the tags and sequences of the others");
+            ui.separator();
+            ScrollArea::vertical().max_height(720.0).show(ui, |ui| {
+                for (gi, group) in win.groups.iter_mut().enumerate() {
+                    ui.group(|ui| {
+                        ui.horizontal_wrapped(|ui| {
+                            for (i, id) in group.ids.iter().enumerate() {
+                                ui.vertical(|ui| {
+                                    let re = ui.add(ImageButton::new(SizedTexture::new(
+                                        TextureId::User(id.0),
+                                        (128.0, 128.0),
+                                    )));
+                                    if group.keep == i {
+                                        ui.painter().rect_stroke(
+                                            re.rect,
+                                            1.0,
+                                            (2.0, Color32::GREEN),
+                                        );
+                                    }
+                                    if re.clicked() {
+                                        group.keep = i;
+                                        group.delete_confirm = false;
+                                    }
+                                    let path = coll.entries[id].path.to_string_lossy();
+                                    let mut text = RichText::new(path).small();
+                                    if group.keep == i {
+                                        text = text.color(Color32::GREEN);
+                                    }
+                                    ui.label(text);
+                                    if group.is_same_file_as_kept(i) {
+                                        ui.label(
+                                            RichText::new("Same file as the kept one").small(),
+                                        )
+                                        .on_hover_text(
+                                            "A link to the kept file. \
+                                                 Only the entry is removed, the file is kept.",
+                                        );
+                                    }
+                                });
+                            }
+                        });
+                        if group.delete_confirm {
+                            ui.label(format!(
+                                "About to delete {} files",
+                                group.n_files_to_delete()
+                            ));
+                            ui.horizontal(|ui| {
+                                if ui
+                                    .add(egui::Button::new("Confirm").fill(Color32::RED))
+                                    .clicked()
+                                {
+                                    action = Some(Action::Resolve {
+                                        group: gi,
+                                        how: Resolution::Delete,
+                                    });
+                                    group.delete_confirm = false;
+                                }
+                                if ui.button("Cancel").clicked() {
+                                    group.delete_confirm = false;
+                                }
+                            });
+                            return;
+                        }
+                        ui.horizontal(|ui| {
+                            if ui.button("Keep selected, delete others").clicked() {
+                                group.delete_confirm = true;
+                            }
+                            if ui
+                                .button("Keep selected, exclude others")
+                                .on_hover_text(
+                                    "Remove the others from the collection, \
+                                     and add them to the ignore patterns",
+                                )
+                                .clicked()
+                            {
+                                action = Some(Action::Resolve {
+                                    group: gi,
+                                    how: Resolution::Exclude,
+                                });
+                            }
+                        });
+                    });
+                }
+            });
+        });
+    let Some(action) = action else {
+        return;
+    };
+    match action {
+        Action::Search => {
+            // Images inside archives aren't files of their own
+            let files = coll
+                .entries
+                .iter()
+                .filter(|(_, en)| archive::split_member(&en.path).is_none())
+                .map(|(id, en)| (*id, root.join(&en.path)))
+                .collect();
+            win.scan = Some(DupScan::start(coll_id, files));
+            win.groups.clear();
+            win.searched = false;
+        }
+        Action::Resolve { group, how } => {
+            let group = win.groups.remove(group);
+            let keep = group.ids[group.keep];
+            let mut merged = Vec::new();
+            let mut errors = Vec::new();
+            for (i, &id) in group.ids.iter().enumerate() {
+                if id == keep {
+                    continue;
+                }
+                let path = &coll.entries[&id].path;
+                match how {
+                    // Deleting a link to the kept file could delete the kept file itself
+                    Resolution::Delete if group.is_same_file_as_kept(i) => {}
+                    Resolution::Delete => {
+                        if let Err(e) = std::fs::remove_file(root.join(path)) {
+                            errors.push(format!("{}: {e}", path.display()));
+                            continue;
+                        }
+                    }
+                    Resolution::Exclude => {
+                        coll.ignore_patterns.push(literal_ignore_pattern(path));
+                    }
+                }
+                merged.push(id);
+            }
+            if let Err(e) = coll.merge_duplicates(keep, &merged) {
+                errors.push(format!("{e:?}"));
+            }
+            // Also remove from selection buffers and the viewer list
+            state.forget_missing_entries(&coll.entries);
+            state
+                .thumbs_view
+                .update_from_collection(coll, &state.filter);
+            state.thumbs_view.clamp_bottom(rw);
+            if !errors.is_empty() {
+                egui_state
+                    .modal
+                    .err(format!("Errors merging duplicates:\n{}", errors.join("\n")));
+            }
+        }
+    }
+}
@@ -6,6 +6,7 @@ use {
         EguiState,
     },
     crate::{
+        archive,
         collection::{AddTagError, Collection, TagsExt},
         db::Db,
         dlog, entry,
@@ -38,6 +39,7 @@ use {
     std::{
         fmt::Write,
         io::Read,
+        path::Path,
         process::{Child, Command, ExitStatus, Stdio},
     },
 };
@@ -243,7 +245,7 @@ pub(super) fn do_frame(
                                 id,
                                 &coll.entries,
                                 &state.thumbnail_loader,
-                                state.thumbs_view.thumb_size,
+                                state.thumbs_view.thumb_bounds(),
                                 res,
                             )
                             .1
@@ -457,6 +459,44 @@ pub(super) fn do_frame(
                             egui_state.sequences_window.pick_mode = false;
                             egui_state.sequences_window.pick_result = None;
                         }
+                        ui.menu_button(concat!(icons::EDIT, " Edit with…"), |ui| {
+                            let mut apps: Vec<_> = db.preferences.applications.values().collect();
+                            apps.sort_by_key(|app| &app.name);
+                            if apps.is_empty() {
+                                ui.label("No applications. Add some in the preferences.");
+                            }
+                            for app in apps {
+                                if !ui.button(&app.name).clicked() {
+                                    continue;
+                                }
+                                ui.close_menu();
+                                // Images inside archives can't be edited in place
+                                let entries: Vec<(entry::Id, &Path)> = win
+                                    .ids
+                                    .iter()
+                                    .filter_map(|id| {
+                                        Some((*id, coll.entries.get(id)?.path.as_path()))
+                                    })
+                                    .filter(|(_, path)| archive::split_member(path).is_none())
+                                    .collect();
+                                if entries.is_empty() {
+                                    egui_state
+                                        .modal
+                                        .err("Images inside archives can't be edited");
+                                    continue;
+                                }
+                                let paths: Vec<&Path> =
+                                    entries.iter().map(|(_, path)| *path).collect();
+                                if let Err(e) = external::open_with_app(app, &paths) {
+                                    egui_state.modal.err(format!("Error opening editor: {e}"));
+                                    continue;
+                                }
+                                // Reload the images when the editor writes them
+                                for (id, path) in entries {
+                                    state.edit_watch.watch(id, path);
+                                }
+                            }
+                        });
                         if ui
                             .add(
                                 Button::new(concat!(icons::TERM, " Run custom command"))
@@ -604,6 +644,28 @@ pub(super) fn do_frame(
                             retain
                         });
                         ui.separator();
+                        if let [id] = win.ids[..]
+                            && ui
+                                .add(
+                                    Button::new(concat!(icons::QUESTION, " Explain query"))
+                                        .wrap_mode(TextWrapMode::Extend),
+                                )
+                                .on_hover_text("Show why this entry matches the filter or not")
+                                .clicked()
+                        {
+                            egui_state.explain_window.explain(id);
+                        }
+                        if let [id] = win.ids[..]
+                            && ui
+                                .add(
+                                    Button::new(concat!(icons::SIMILAR, " Find similar"))
+                                        .wrap_mode(TextWrapMode::Extend),
+                                )
+                                .on_hover_text("Find images that look like this one")
+                                .clicked()
+                        {
+                            egui_state.similar_window.find_similar_to(id);
+                        }
                         // region: Rename button
                         if win.ids.len() == 1 {
                             if ui
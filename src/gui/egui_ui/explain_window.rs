@@ -0,0 +1,144 @@
+use {
+    super::{icons, EguiState},
+    crate::{
+        collection::{Collection, TagsExt},
+        entry::{self, Entry},
+        filter_reqs::Req,
+        gui::State,
+    },
+    egui_sfml::egui::{
+        self, load::SizedTexture, Color32, Image, RichText, ScrollArea, TextureId, Ui,
+    },
+    std::borrow::Cow,
+};
+
+/// Shows why an entry matches (or doesn't match) the current filter or find query
+#[derive(Default)]
+pub struct ExplainWindow {
+    pub open: bool,
+    pub entry: Option<entry::Id>,
+    /// Explain the find query instead of the filter
+    pub find: bool,
+}
+
+impl ExplainWindow {
+    pub fn explain(&mut self, id: entry::Id) {
+        self.open = true;
+        self.entry = Some(id);
+    }
+}
+
+pub(super) fn do_frame(
+    state: &State,
+    egui_state: &mut EguiState,
+    coll: &Collection,
+    egui_ctx: &egui::Context,
+) {
+    let win = &mut egui_state.explain_window;
+    if !win.open {
+        return;
+    }
+    egui::Window::new("Explain query")
+        .open(&mut win.open)
+        .show(egui_ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut win.find, false, "Filter");
+                ui.selectable_value(&mut win.find, true, "Find");
+                ui.separator();
+                if ui.button("Use first selected entry").clicked()
+                    && let Some(&id) = state.sel.current().and_then(|buf| buf.as_vec().first())
+                {
+                    win.entry = Some(id);
+                }
+            });
+            ui.separator();
+            let Some(id) = win.entry else {
+                ui.label("No entry chosen. Use \"Explain query\" in an entries window.");
+                return;
+            };
+            let Some(en) = coll.entries.get(&id) else {
+                ui.label(format!("Dangling entry id: {id:?}"));
+                return;
+            };
+            ui.horizontal(|ui| {
+                ui.add(Image::new(SizedTexture::new(
+                    TextureId::User(id.0),
+                    (128., 128.),
+                )));
+                ui.label(en.path.to_string_lossy());
+            });
+            let reqs = if win.find {
+                &state.find_reqs
+            } else {
+                &state.filter
+            };
+            if reqs.is_empty() {
+                ui.label("The query is empty, so every entry matches");
+                return;
+            }
+            let matches = en.all_reqs_satisfied(id, reqs, &coll.tags, &coll.sequences);
+            ui.horizontal(|ui| {
+                result_label(ui, matches);
+                ui.label(if matches {
+                    "Entry matches the query"
+                } else {
+                    "Entry doesn't match the query"
+                });
+            });
+            ui.separator();
+            ScrollArea::vertical().show(ui, |ui| {
+                let salt = egui::Id::new("explain_tree");
+                for (i, req) in reqs.iter().enumerate() {
+                    req_ui(ui, req, en, id, coll, salt.with(i));
+                }
+            });
+        });
+}
+
+fn result_label(ui: &mut Ui, satisfied: bool) {
+    let (icon, color) = if satisfied {
+        (icons::CHECK, Color32::GREEN)
+    } else {
+        (icons::CANCEL, Color32::RED)
+    };
+    ui.label(RichText::new(icon).color(color));
+}
+
+fn req_ui(ui: &mut Ui, req: &Req, en: &Entry, id: entry::Id, coll: &Collection, salt: egui::Id) {
+    let satisfied = en.req_satisfied(id, req, &coll.tags, &coll.sequences);
+    let label: Cow<str> = match req {
+        Req::Any(_) => "@any".into(),
+        Req::All(_) => "@all".into(),
+        Req::None(_) => "@none".into(),
+        Req::Not(_) => "!".into(),
+        Req::Saved { name, .. } => format!("@saved[{name}]").into(),
+        _ => req.to_string(&coll.tags),
+    };
+    ui.horizontal(|ui| {
+        result_label(ui, satisfied);
+        ui.label(label);
+        if let Req::Tag(tag_id) | Req::Implied(tag_id) = req
+            && satisfied
+            && let Some(path) = en.implication_path(*tag_id, &coll.tags)
+            && path.len() > 1
+        {
+            let names: Vec<Cow<str>> = path.iter().map(|id| coll.tags.first_name_of(id)).collect();
+            ui.label(RichText::new(names.join(" → ")).color(Color32::GRAY));
+        }
+    });
+    match req {
+        Req::Any(reqs) | Req::All(reqs) | Req::None(reqs) | Req::Saved { reqs, .. } => {
+            ui.indent(salt, |ui| {
+                for (i, child) in reqs.iter().enumerate() {
+                    req_ui(ui, child, en, id, coll, salt.with(i));
+                }
+            });
+        }
+        Req::Not(inner) => {
+            ui.indent(salt, |ui| {
+                req_ui(ui, inner, en, id, coll, salt.with(0));
+            });
+        }
+        _ => {}
+    }
+}
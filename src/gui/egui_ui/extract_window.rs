@@ -0,0 +1,132 @@
+use {
+    super::{icons, EguiState, FileOp},
+    crate::{application::Application, coll_extract, filter_reqs::Requirements, gui::State},
+    constcat::concat,
+    egui_sfml::egui::{self, Color32, RichText, TextEdit},
+    std::path::PathBuf,
+};
+
+/// Creates a new collection from the entries of the active one that match a query
+#[derive(Default)]
+pub struct ExtractWindow {
+    pub open: bool,
+    query: String,
+    /// Root folder of the new collection
+    pub new_root: String,
+    copy_files: bool,
+}
+
+pub(super) fn do_frame(
+    state: &State,
+    egui_state: &mut EguiState,
+    app: &mut Application,
+    egui_ctx: &egui::Context,
+) {
+    let win = &mut egui_state.extract_window;
+    if !win.open {
+        return;
+    }
+    let Some((_, coll)) = &app.active_collection else {
+        win.open = false;
+        return;
+    };
+    let mut reqs = Requirements::default();
+    let parsed = reqs
+        .parse_and_resolve(&win.query, coll)
+        .map_err(|e| e.to_string());
+    let mut browse = false;
+    let mut extract = false;
+    egui::Window::new("Extract into new collection")
+        .open(&mut win.open)
+        .show(egui_ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.add(TextEdit::singleline(&mut win.query).hint_text("Query"));
+                if ui.button("Use current filter").clicked() {
+                    win.query = state.filter.to_string(&coll.tags);
+                }
+            });
+            match &parsed {
+                Ok(()) => {
+                    ui.label(format!("{} matching entries", coll.filter(&reqs).count()));
+                }
+                Err(e) => {
+                    ui.label(RichText::new(e).color(Color32::RED));
+                }
+            }
+            ui.horizontal(|ui| {
+                ui.label("New root");
+                ui.add(TextEdit::singleline(&mut win.new_root).hint_text("Folder"));
+                if ui.button(icons::FOLDER).on_hover_text("Browse").clicked() {
+                    browse = true;
+                }
+            });
+            ui.checkbox(&mut win.copy_files, "Copy the files to the new root")
+                .on_hover_text("Otherwise the files have to be inside the new root already");
+            ui.separator();
+            if ui
+                .add_enabled(
+                    parsed.is_ok() && !win.new_root.is_empty(),
+                    egui::Button::new(concat!(icons::CHECK, " Extract")),
+                )
+                .clicked()
+            {
+                extract = true;
+            }
+        });
+    if browse {
+        egui_state.file_dialog.select_directory();
+        egui_state.file_op = Some(FileOp::ExtractRoot);
+    }
+    if !extract {
+        return;
+    }
+    let Some((coll_id, coll)) = &app.active_collection else {
+        return;
+    };
+    let Some(root) = app.database.collections.get(coll_id) else {
+        egui_state.modal.err("Active collection has no root");
+        return;
+    };
+    let new_root = PathBuf::from(win.new_root.trim());
+    if !new_root.is_absolute() {
+        egui_state
+            .modal
+            .err("The new root must be an absolute path");
+        return;
+    }
+    if app.database.find_collection_by_path(&new_root).is_some() {
+        egui_state
+            .modal
+            .err("There is already a collection with that root");
+        return;
+    }
+    if !win.copy_files && !new_root.is_dir() {
+        egui_state.modal.err("The new root doesn't exist");
+        return;
+    }
+    let (new_coll, errors) = coll_extract::extract(
+        coll,
+        root,
+        &reqs,
+        &new_root,
+        win.copy_files,
+        &mut app.database.uid_counter,
+    );
+    let n_entries = new_coll.entries.len();
+    if let Err(e) = app.add_inactive_collection(&new_coll, new_root) {
+        egui_state
+            .modal
+            .err(format!("Failed to save new collection: {e:?}"));
+        return;
+    }
+    if errors.is_empty() {
+        egui_state.modal.success(format!(
+            "Extracted {n_entries} entries into a new collection"
+        ));
+    } else {
+        egui_state.modal.err(format!(
+            "Extracted {n_entries} entries, but some were left out:\n{}",
+            errors.join("\n")
+        ));
+    }
+}
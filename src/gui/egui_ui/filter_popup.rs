@@ -1,16 +1,18 @@
 use {
-    super::EguiState,
+    super::{icons, EguiState},
     crate::{
         collection::Collection,
+        filter_reqs,
         gui::{
             egui_ui::{
                 entries_window::text_edit_cursor_set_to_end,
+                query_popup::{describe_err, highlight_query},
                 tag_autocomplete::tag_autocomplete_popup,
             },
             State,
         },
     },
-    egui_sfml::egui::{Color32, Context, Key, Modifiers, TextEdit},
+    egui_sfml::egui::{Color32, ComboBox, Context, Key, Modifiers, RichText, TextEdit, Ui},
 };
 
 /// Returns whether filter state changed
@@ -22,6 +24,7 @@ pub(super) fn do_frame(
 ) -> bool {
     let mut text_changed = false;
     let mut success = false;
+    let mut explain = false;
     egui_state
         .filter_popup
         .do_frame("filter", egui_ctx, |popup, ui| {
@@ -30,10 +33,13 @@ pub(super) fn do_frame(
                 ui.input_mut(|inp| inp.consume_key(Modifiers::default(), Key::ArrowUp));
             let down_pressed =
                 ui.input_mut(|inp| inp.consume_key(Modifiers::default(), Key::ArrowDown));
-            let mut te = TextEdit::singleline(&mut popup.string).lock_focus(true);
-            if count == 0 {
-                te = te.text_color(Color32::RED);
-            }
+            let mut layouter = |ui: &Ui, text: &str, _wrap_width: f32| {
+                let job = highlight_query(ui, text, coll, popup.err_span.as_ref());
+                ui.fonts(|fonts| fonts.layout_job(job))
+            };
+            let te = TextEdit::singleline(&mut popup.string)
+                .lock_focus(true)
+                .layouter(&mut layouter);
             let re = ui.add(te);
             if popup.ac_state.applied {
                 text_edit_cursor_set_to_end(ui, re.id);
@@ -50,20 +56,50 @@ pub(super) fn do_frame(
                 state.wipe_search();
                 text_changed = true;
             }
-            ui.label(format!("{count} results"));
-            popup.string.make_ascii_lowercase();
+            if !coll.saved_searches.is_empty() {
+                let mut names: Vec<&String> = coll.saved_searches.keys().collect();
+                names.sort();
+                ComboBox::from_id_salt("saved_search_combo")
+                    .selected_text("Saved")
+                    .show_ui(ui, |ui| {
+                        for name in names {
+                            let query = &coll.saved_searches[name];
+                            if ui
+                                .selectable_label(false, name)
+                                .on_hover_text(query)
+                                .clicked()
+                            {
+                                popup.string.clone_from(query);
+                                text_changed = true;
+                            }
+                        }
+                    });
+            }
+            let mut count_text = RichText::new(format!("{count} results"));
+            if count == 0 {
+                count_text = count_text.color(Color32::RED);
+            }
+            ui.label(count_text);
+            if ui
+                .button(icons::QUESTION)
+                .on_hover_text("Explain the filter for the first selected entry")
+                .clicked()
+            {
+                explain = true;
+            }
+            filter_reqs::lowercase_tags(&mut popup.string);
             let enter_pressed = egui_ctx.input(|inp| inp.key_pressed(Key::Enter));
             if enter_pressed || egui_ctx.input(|inp| inp.key_pressed(Key::Escape)) {
                 popup.on = false;
             }
             if re.changed() || text_changed || enter_pressed {
-                popup.err_string.clear();
+                popup.clear_err();
                 match state.filter.parse_and_resolve(&popup.string, coll) {
                     Ok(()) => {
                         success = true;
                     }
                     Err(e) => {
-                        popup.err_string = format!("Error: {e}");
+                        (popup.err_string, popup.err_span) = describe_err(&e, &popup.string, coll);
                         success = false;
                     }
                 }
@@ -73,5 +109,14 @@ pub(super) fn do_frame(
             }
             ui.memory_mut(|mem| mem.request_focus(re.id));
         });
+    if explain {
+        let win = &mut egui_state.explain_window;
+        win.open = true;
+        // It was opened from the filter popup, so it's the filter that needs explaining
+        win.find = false;
+        if let Some(&id) = state.sel.current().and_then(|buf| buf.as_vec().first()) {
+            win.entry = Some(id);
+        }
+    }
     text_changed && success
 }
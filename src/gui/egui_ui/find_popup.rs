@@ -1,14 +1,17 @@
 use {
     super::{
-        entries_window::text_edit_cursor_set_to_end, tag_autocomplete::tag_autocomplete_popup,
+        entries_window::text_edit_cursor_set_to_end,
+        query_popup::{describe_err, highlight_query},
+        tag_autocomplete::tag_autocomplete_popup,
         EguiState,
     },
     crate::{
         collection::Collection,
+        filter_reqs,
         gui::{thumbnails_view::search_goto_cursor, State},
     },
     egui_sfml::{
-        egui::{Context, Key, Modifiers, TextEdit},
+        egui::{Context, Key, Modifiers, TextEdit, Ui},
         sfml::graphics::{RenderTarget, RenderWindow},
     },
 };
@@ -27,7 +30,13 @@ pub(super) fn do_frame(
                 ui.input_mut(|inp| inp.consume_key(Modifiers::default(), Key::ArrowUp));
             let down_pressed =
                 ui.input_mut(|inp| inp.consume_key(Modifiers::default(), Key::ArrowDown));
-            let te = TextEdit::singleline(&mut popup.string).lock_focus(true);
+            let mut layouter = |ui: &Ui, text: &str, _wrap_width: f32| {
+                let job = highlight_query(ui, text, coll, popup.err_span.as_ref());
+                ui.fonts(|fonts| fonts.layout_job(job))
+            };
+            let te = TextEdit::singleline(&mut popup.string)
+                .lock_focus(true)
+                .layouter(&mut layouter);
             let re = ui.add(te);
             if popup.ac_state.applied {
                 text_edit_cursor_set_to_end(ui, re.id);
@@ -45,13 +54,13 @@ pub(super) fn do_frame(
                 state.wipe_search();
                 text_changed = true;
             }
-            popup.string.make_ascii_lowercase();
+            filter_reqs::lowercase_tags(&mut popup.string);
             let enter_pressed = egui_ctx.input(|inp| inp.key_pressed(Key::Enter));
             if enter_pressed || egui_ctx.input(|inp| inp.key_pressed(Key::Escape)) {
                 popup.on = false;
             }
             if re.changed() || text_changed || enter_pressed {
-                popup.err_string.clear();
+                popup.clear_err();
                 match state.find_reqs.parse_and_resolve(&popup.string, coll) {
                     Ok(()) => {
                         if enter_pressed {
@@ -60,7 +69,7 @@ pub(super) fn do_frame(
                         }
                     }
                     Err(e) => {
-                        popup.err_string = format!("Error: {e}");
+                        (popup.err_string, popup.err_span) = describe_err(&e, &popup.string, coll);
                     }
                 }
                 popup.ac_state.input_changed = true;
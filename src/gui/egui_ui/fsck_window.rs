@@ -0,0 +1,110 @@
+use {
+    super::{icons, EguiState},
+    crate::{application::Application, fsck, gui::State},
+    constcat::concat,
+    egui_sfml::{
+        egui::{self, Grid, ScrollArea},
+        sfml::graphics::RenderWindow,
+    },
+};
+
+/// Finds inconsistencies in the active collection, and offers to repair them
+#[derive(Default)]
+pub struct FsckWindow {
+    pub open: bool,
+    /// Result of the last check, `None` if not checked yet
+    problems: Option<Vec<fsck::Problem>>,
+}
+
+enum Action {
+    Check,
+    Repair(usize),
+    RepairAll,
+}
+
+pub(super) fn do_frame(
+    state: &mut State,
+    egui_state: &mut EguiState,
+    app: &mut Application,
+    egui_ctx: &egui::Context,
+    rw: &RenderWindow,
+) {
+    let win = &mut egui_state.fsck_window;
+    if !win.open {
+        return;
+    }
+    let Some((_, coll)) = &mut app.active_collection else {
+        win.open = false;
+        return;
+    };
+    let mut action = None;
+    egui::Window::new("Check collection")
+        .open(&mut win.open)
+        .show(egui_ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Run check").clicked() {
+                    action = Some(Action::Check);
+                }
+                if ui
+                    .add_enabled(
+                        win.problems.as_ref().is_some_and(|probs| !probs.is_empty()),
+                        egui::Button::new(concat!(icons::CHECK, " Repair all")),
+                    )
+                    .clicked()
+                {
+                    action = Some(Action::RepairAll);
+                }
+            });
+            let Some(problems) = &win.problems else {
+                return;
+            };
+            ui.separator();
+            if problems.is_empty() {
+                ui.label("No problems found");
+                return;
+            }
+            ui.label(format!("{} problems found", problems.len()));
+            ScrollArea::vertical().max_height(480.0).show(ui, |ui| {
+                Grid::new("fsck_grid")
+                    .num_columns(2)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (i, problem) in problems.iter().enumerate() {
+                            ui.label(problem.description(coll, &app.database));
+                            if ui.button(problem.repair_label()).clicked() {
+                                action = Some(Action::Repair(i));
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+    let Some(action) = action else {
+        return;
+    };
+    let result: anyhow::Result<()> = try {
+        match action {
+            Action::Check => {}
+            Action::Repair(idx) => {
+                if let Some(problem) = win.problems.as_ref().and_then(|probs| probs.get(idx)) {
+                    problem.repair(coll, &mut app.database)?;
+                }
+            }
+            Action::RepairAll => {
+                for problem in win.problems.iter().flatten() {
+                    problem.repair(coll, &mut app.database)?;
+                }
+            }
+        }
+    };
+    if let Err(e) = result {
+        egui_state.modal.err(format!("Repair failed: {e}"));
+    }
+    win.problems = Some(fsck::check(coll, &app.database));
+    // Repairing duplicate paths deletes entries
+    state.forget_missing_entries(&coll.entries);
+    state
+        .thumbs_view
+        .update_from_collection(coll, &state.filter);
+    state.thumbs_view.clamp_bottom(rw);
+}
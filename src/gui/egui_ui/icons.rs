@@ -18,3 +18,8 @@ pub const HAMBURGER: &str = "☰";
 pub const CABINET: &str = "🗄";
 pub const SAVE: &str = "💾";
 pub const SORT: &str = "♻";
+pub const SEARCH: &str = "🔍";
+pub const WRENCH: &str = "🔧";
+pub const DUPLICATE: &str = "⧉";
+pub const SIMILAR: &str = "≈";
+pub const MOVE: &str = "📁";
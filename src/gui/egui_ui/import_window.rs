@@ -0,0 +1,184 @@
+use {
+    super::{
+        entries_window::text_edit_cursor_set_to_end,
+        icons,
+        tag_autocomplete::{tag_autocomplete_popup, AcState},
+        EguiState, FileOp,
+    },
+    crate::{
+        collection::Collection,
+        db::UidCounter,
+        gui::State,
+        import::{self, ImportMode},
+    },
+    constcat::concat,
+    egui_sfml::{
+        egui::{self, Key, Modifiers, ScrollArea, TextEdit},
+        sfml::graphics::RenderWindow,
+    },
+    std::path::{Path, PathBuf},
+};
+
+/// Copies or moves external files and folders into the collection, tagging them
+#[derive(Default)]
+pub struct ImportWindow {
+    pub open: bool,
+    /// Absolute paths of the files and folders to import
+    pub sources: Vec<PathBuf>,
+    /// For typing in a source path
+    path_buf: String,
+    /// Subfolder of the collection root to import into
+    dest: String,
+    mode: ImportMode,
+    tags_buf: String,
+    ac_state: AcState,
+}
+
+pub(super) fn do_frame(
+    state: &mut State,
+    egui_state: &mut EguiState,
+    coll: &mut Collection,
+    uid_counter: &mut UidCounter,
+    egui_ctx: &egui::Context,
+    rw: &RenderWindow,
+) {
+    let win = &mut egui_state.import_window;
+    if !win.open {
+        return;
+    }
+    let mut pick_dir = None;
+    let mut import = false;
+    egui::Window::new("Import")
+        .open(&mut win.open)
+        .show(egui_ctx, |ui| {
+            ui.heading("Sources");
+            let mut remove = None;
+            ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+                for (i, src) in win.sources.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.button(icons::REMOVE).on_hover_text("Remove").clicked() {
+                            remove = Some(i);
+                        }
+                        ui.label(src.to_string_lossy());
+                    });
+                }
+            });
+            if let Some(idx) = remove {
+                win.sources.remove(idx);
+            }
+            ui.horizontal(|ui| {
+                if ui.button(concat!(icons::ADD, " File")).clicked() {
+                    pick_dir = Some(false);
+                }
+                if ui.button(concat!(icons::FOLDER, " Folder")).clicked() {
+                    pick_dir = Some(true);
+                }
+                let re = ui.add(TextEdit::singleline(&mut win.path_buf).hint_text("Path"));
+                if (ui.button("Add").clicked()
+                    || (re.lost_focus() && ui.input(|inp| inp.key_pressed(Key::Enter))))
+                    && !win.path_buf.is_empty()
+                {
+                    win.sources
+                        .push(PathBuf::from(std::mem::take(&mut win.path_buf)));
+                }
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Into subfolder");
+                ui.add(TextEdit::singleline(&mut win.dest).hint_text("Collection root"));
+            });
+            ui.horizontal(|ui| {
+                for mode in ImportMode::ALL {
+                    ui.radio_value(&mut win.mode, mode, mode.label());
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label(concat!(icons::TAG, " Tags"));
+                let te_id = ui.make_persistent_id("import_tags_edit");
+                let focused = ui.memory(|mem| mem.has_focus(te_id));
+                let up_pressed = focused
+                    && ui.input_mut(|inp| inp.consume_key(Modifiers::default(), Key::ArrowUp));
+                let down_pressed = focused
+                    && ui.input_mut(|inp| inp.consume_key(Modifiers::default(), Key::ArrowDown));
+                let te = TextEdit::singleline(&mut win.tags_buf)
+                    .hint_text("tag1 tag2 tag3 ...")
+                    .id(te_id);
+                if win.ac_state.applied {
+                    text_edit_cursor_set_to_end(ui, te_id);
+                }
+                let re = ui.add(te);
+                if re.changed() {
+                    win.ac_state.input_changed = true;
+                }
+                if focused {
+                    tag_autocomplete_popup(
+                        &mut win.tags_buf,
+                        &mut win.ac_state,
+                        coll,
+                        ui,
+                        &re,
+                        up_pressed,
+                        down_pressed,
+                    );
+                }
+                win.tags_buf.make_ascii_lowercase();
+            });
+            ui.separator();
+            if ui
+                .add_enabled(
+                    !win.sources.is_empty(),
+                    egui::Button::new(concat!(icons::CHECK, " Import")),
+                )
+                .on_hover_text("Files whose name is taken get a number added to their name")
+                .clicked()
+            {
+                import = true;
+            }
+        });
+    if let Some(dir) = pick_dir {
+        if dir {
+            egui_state.file_dialog.select_directory();
+        } else {
+            egui_state.file_dialog.select_file();
+        }
+        egui_state.file_op = Some(FileOp::ImportSource);
+    }
+    if !import {
+        return;
+    }
+    let result: anyhow::Result<(usize, Vec<String>)> = try {
+        let tags = import::resolve_tags(coll, &win.tags_buf)?;
+        let plan = import::plan(coll, &win.sources, Path::new(win.dest.trim()))?;
+        let renamed = plan.renamed;
+        (
+            renamed,
+            import::execute(coll, uid_counter, plan, win.mode, &tags),
+        )
+    };
+    let (renamed, errors) = match result {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            egui_state.modal.err(format!("{e}"));
+            return;
+        }
+    };
+    win.sources.clear();
+    state
+        .thumbs_view
+        .update_from_collection(coll, &state.filter);
+    state.thumbs_view.clamp_bottom(rw);
+    if errors.is_empty() {
+        let mut msg = String::from("Import done");
+        if renamed != 0 {
+            msg.push_str(&format!(
+                ", {renamed} files were renamed to avoid name collisions"
+            ));
+        }
+        egui_state.modal.success(msg);
+    } else {
+        egui_state.modal.err(format!(
+            "Some files couldn't be imported:\n{}",
+            errors.join("\n")
+        ));
+    }
+}
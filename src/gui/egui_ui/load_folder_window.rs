@@ -1,20 +1,21 @@
 use {
-    super::{icons, EguiState},
+    super::{coll_prefs_window::link_policy_ui, icons, EguiState},
     crate::{
         application::Application,
-        collection::Collection,
-        dlog,
-        folder_scan::walkdir,
+        collection::{Collection, LinkPolicy},
+        dlog, exif_meta,
+        folder_scan::{file_id, is_loop_err, walk_builder, DEFAULT_IGNORE_PATTERNS},
         gui::{resources::Resources, thumbnail_loader, State},
     },
     constcat::concat,
     egui_sfml::{
         egui::{
             self, vec2, Align, Button, Color32, Context, Key, Label, ProgressBar, RichText,
-            ScrollArea, Sense, Window,
+            ScrollArea, Sense, TextEdit, Window,
         },
         sfml::{cpp::FBox, graphics::Texture},
     },
+    fnv::FnvHashSet,
     std::{
         ffi::OsStr,
         io, mem,
@@ -38,6 +39,9 @@ pub struct LoadFolderWindow {
     res_hover: Option<usize>,
     pub texture: Option<FBox<Texture>>,
     ign_ext_buf: String,
+    /// Gitignore-style patterns, one per line
+    ign_patterns_buf: String,
+    link_policy: LinkPolicy,
 }
 
 struct PathAdd {
@@ -55,8 +59,15 @@ type PathAddResult = io::Result<PathAdd>;
 
 fn start_loading(win: &mut LoadFolderWindow) {
     let path_clone = win.root.clone();
+    let patterns = win.ign_patterns_buf.to_pattern_vec();
+    let link_policy = win.link_policy;
     let (sender, receiver) = channel();
-    let join_handle = std::thread::spawn(move || read_dir_entries(path_clone.as_ref(), &sender));
+    win.results.clear();
+    win.res_select = None;
+    win.res_hover = None;
+    let join_handle = std::thread::spawn(move || {
+        read_dir_entries(path_clone.as_ref(), &patterns, link_policy, &sender)
+    });
     let loading_state = LoadingState {
         join_handle: Some(join_handle),
         receiver,
@@ -68,6 +79,7 @@ pub(super) fn open(win: &mut LoadFolderWindow, path: PathBuf) {
     let path_arc = Arc::new(path);
     win.open = true;
     win.root = path_arc;
+    win.ign_patterns_buf = DEFAULT_IGNORE_PATTERNS.join("\n");
     start_loading(win);
 }
 
@@ -160,7 +172,11 @@ pub(super) fn do_frame(
                                         }
                                         if did_select_new {
                                             if let Ok(image) =
-                                                image::open(win.root.join(&path.path))
+                                                std::fs::read(win.root.join(&path.path))
+                                                    .map_err(image::ImageError::IoError)
+                                                    .and_then(|data| {
+                                                        exif_meta::load_oriented(&data)
+                                                    })
                                             {
                                                 let buf = image.to_rgba8();
                                                 let tex = thumbnail_loader::imagebuf_to_sf_tex(buf);
@@ -203,6 +219,16 @@ pub(super) fn do_frame(
                     });
                 }
             });
+            ui.horizontal(|ui| {
+                ui.label("Ignore patterns (gitignore syntax, one per line)");
+                ui.add(TextEdit::multiline(&mut win.ign_patterns_buf).desired_rows(2));
+                ui.vertical(|ui| {
+                    link_policy_ui(ui, &mut win.link_policy);
+                });
+                if ui.button("⟳ Rescan").clicked() {
+                    start_loading(win);
+                }
+            });
             ui.separator();
             ui.horizontal(|ui| {
                 if ui.button(icons::CANCEL_TEXT).clicked() {
@@ -233,6 +259,8 @@ pub(super) fn do_frame(
                             .into_iter()
                             .map(ToOwned::to_owned)
                             .collect();
+                        coll.ignore_patterns = win.ign_patterns_buf.to_pattern_vec();
+                        coll.link_policy = win.link_policy;
                         let id = app.add_collection(coll, (*win.root).clone());
                         if let Err(e) = crate::gui::set_active_collection(
                             &mut state.thumbs_view,
@@ -287,17 +315,40 @@ fn update(load_state: &mut LoadingState, result_vec: &mut Vec<Result<PathAdd, io
     false
 }
 
-fn read_dir_entries(root: &Path, sender: &Sender<PathResult>) -> anyhow::Result<()> {
-    let wd = walkdir(root);
-    for dir_entry in wd {
+fn read_dir_entries(
+    root: &Path,
+    ignore_patterns: &[String],
+    link_policy: LinkPolicy,
+    sender: &Sender<PathResult>,
+) -> anyhow::Result<()> {
+    let walk = match walk_builder(root, root, ignore_patterns, link_policy) {
+        Ok(mut builder) => builder.sort_by_file_name(|a, b| a.cmp(b)).build(),
+        Err(e) => {
+            // Show bad patterns in the results list
+            sender.send(Err(io::Error::other(format!("{e:#}"))))?;
+            return Ok(());
+        }
+    };
+    let mut seen_ids = FnvHashSet::default();
+    for dir_entry in walk {
         let dir_entry = match dir_entry {
             Ok(en) => en,
+            Err(e) if is_loop_err(&e) => {
+                dlog!("Skipping symlink loop: {e}");
+                continue;
+            }
             Err(e) => {
-                sender.send(Err(e.into()))?;
+                sender.send(Err(io::Error::other(e)))?;
                 continue;
             }
         };
-        if dir_entry.file_type().is_dir() {
+        if dir_entry.file_type().is_some_and(|ft| ft.is_dir()) {
+            continue;
+        }
+        if link_policy.dedup_inodes
+            && let Some(id) = file_id(dir_entry.path())
+            && !seen_ids.insert(id)
+        {
             continue;
         }
         let dir_entry_path = dir_entry.into_path();
@@ -315,10 +366,18 @@ fn read_dir_entries(root: &Path, sender: &Sender<PathResult>) -> anyhow::Result<
 
 trait IgnoreStrExt {
     fn to_ignore_vec(&self) -> Vec<&str>;
+    fn to_pattern_vec(&self) -> Vec<String>;
 }
 
 impl IgnoreStrExt for str {
     fn to_ignore_vec(&self) -> Vec<&str> {
         self.split(',').map(str::trim).collect()
     }
+    fn to_pattern_vec(&self) -> Vec<String> {
+        self.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(ToOwned::to_owned)
+            .collect()
+    }
 }
@@ -0,0 +1,289 @@
+use {
+    super::{icons, EguiState},
+    crate::{
+        application::Application,
+        coll_merge::{self, MergePlan, OutsideFiles},
+        collection::{self, Collection, TagsExt},
+        gui::State,
+        move_rules::is_inside_root,
+        tag,
+    },
+    constcat::concat,
+    egui_sfml::{
+        egui::{self, ComboBox, Grid, ScrollArea, TextEdit},
+        sfml::graphics::RenderWindow,
+    },
+    std::path::{Path, PathBuf},
+};
+
+/// Merges another collection into the active one
+#[derive(Default)]
+pub struct MergeWindow {
+    pub open: bool,
+    /// The collection to merge in
+    source: Option<collection::Id>,
+    /// The loaded source collection along with its plan, for reviewing before merging
+    loaded: Option<(Collection, MergePlan)>,
+    outside: OutsideFiles,
+    /// Subfolder of the target root that files from outside of it end up in
+    subfolder: String,
+    /// Remove the merged collection after moving its files out, instead of keeping the rest
+    remove_after_move: bool,
+}
+
+enum Action {
+    Analyze,
+    Merge,
+}
+
+pub(super) fn do_frame(
+    state: &mut State,
+    egui_state: &mut EguiState,
+    app: &mut Application,
+    egui_ctx: &egui::Context,
+    rw: &RenderWindow,
+) {
+    let win = &mut egui_state.merge_window;
+    if !win.open {
+        return;
+    }
+    let Some((target_id, target)) = &app.active_collection else {
+        win.open = false;
+        return;
+    };
+    let mut action = None;
+    egui::Window::new("Merge collections")
+        .open(&mut win.open)
+        .show(egui_ctx, |ui| {
+            ui.label("Merge another collection into the active one");
+            let label = |id: &collection::Id| match app.database.collections.get(id) {
+                Some(path) => path.display().to_string(),
+                None => format!("<{}>", id.0),
+            };
+            ui.horizontal(|ui| {
+                let prev = win.source;
+                ComboBox::new("merge_source_combo", "Collection to merge")
+                    .selected_text(win.source.as_ref().map(label).unwrap_or_default())
+                    .show_ui(ui, |ui| {
+                        for id in app.database.collections.keys() {
+                            if id != target_id {
+                                ui.selectable_value(&mut win.source, Some(*id), label(id));
+                            }
+                        }
+                    });
+                if win.source != prev {
+                    win.loaded = None;
+                }
+                if ui
+                    .add_enabled(win.source.is_some(), egui::Button::new("Analyze"))
+                    .clicked()
+                {
+                    action = Some(Action::Analyze);
+                }
+            });
+            let Some((source, plan)) = &mut win.loaded else {
+                return;
+            };
+            ui.separator();
+            ui.label(format!(
+                "{} entries inside the root of the active collection, {} outside",
+                plan.n_inside, plan.n_outside
+            ));
+            ui.label(format!(
+                "{} tags matched by name, {} new",
+                source.tags.len() - plan.n_new_tags - plan.conflicts.len(),
+                plan.n_new_tags
+            ));
+            if plan.n_outside != 0 {
+                ui.horizontal(|ui| {
+                    ui.label("Files outside");
+                    for outside in OutsideFiles::ALL {
+                        ui.radio_value(&mut win.outside, outside, outside.label());
+                    }
+                });
+                if win.outside != OutsideFiles::Skip {
+                    ui.horizontal(|ui| {
+                        ui.label("Into subfolder");
+                        ui.add(
+                            TextEdit::singleline(&mut win.subfolder).hint_text("Collection root"),
+                        );
+                    });
+                }
+                if win.outside == OutsideFiles::Move {
+                    ui.horizontal(|ui| {
+                        ui.label("Afterwards");
+                        ui.radio_value(
+                            &mut win.remove_after_move,
+                            false,
+                            "Keep the merged collection without the moved files",
+                        );
+                        ui.radio_value(
+                            &mut win.remove_after_move,
+                            true,
+                            "Remove the merged collection",
+                        );
+                    });
+                }
+            }
+            if !plan.conflicts.is_empty() {
+                ui.separator();
+                ui.heading("Tag conflicts");
+                ui.label(
+                    "The names of these tags belong to different tags. Pick one to merge into.",
+                );
+                ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+                    Grid::new("merge_conflicts_grid")
+                        .num_columns(2)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for conflict in &mut plan.conflicts {
+                                let names = source
+                                    .tags
+                                    .get(&conflict.tag)
+                                    .map(|tag| tag.names.join(", "))
+                                    .unwrap_or_default();
+                                ui.label(names);
+                                let cand_label = |id: &tag::Id| {
+                                    target
+                                        .tags
+                                        .get(id)
+                                        .map(|tag| tag.names.join(", "))
+                                        .unwrap_or_else(|| {
+                                            target.tags.first_name_of(id).into_owned()
+                                        })
+                                };
+                                ComboBox::from_id_salt(("merge_conflict", conflict.tag))
+                                    .selected_text(cand_label(
+                                        &conflict.candidates[conflict.choice],
+                                    ))
+                                    .show_ui(ui, |ui| {
+                                        for (i, cand) in conflict.candidates.iter().enumerate() {
+                                            ui.selectable_value(
+                                                &mut conflict.choice,
+                                                i,
+                                                cand_label(cand),
+                                            );
+                                        }
+                                    });
+                                ui.end_row();
+                            }
+                        });
+                });
+            }
+            ui.separator();
+            if ui.button(concat!(icons::CHECK, " Merge")).clicked() {
+                action = Some(Action::Merge);
+            }
+        });
+    let Some(action) = action else {
+        return;
+    };
+    let Some(source_id) = win.source else {
+        return;
+    };
+    let Some(source_root) = app.database.collections.get(&source_id).cloned() else {
+        egui_state.modal.err("No collection with such id");
+        return;
+    };
+    let Some(root) = app.database.collections.get(target_id).cloned() else {
+        egui_state.modal.err("Active collection has no root");
+        return;
+    };
+    match action {
+        Action::Analyze => match app.read_collection(source_id) {
+            Ok(source) => {
+                if win.subfolder.is_empty()
+                    && let Some(name) = source_root.file_name()
+                {
+                    win.subfolder = name.to_string_lossy().into_owned();
+                }
+                let plan = coll_merge::plan(target, &root, &source, &source_root);
+                win.loaded = Some((source, plan));
+            }
+            Err(e) => egui_state.modal.err(format!("{e:?}")),
+        },
+        Action::Merge => {
+            let subfolder = Path::new(win.subfolder.trim());
+            if !is_inside_root(subfolder) {
+                egui_state
+                    .modal
+                    .err("Destination folder must be inside the collection");
+                return;
+            }
+            let Some((source, plan)) = win.loaded.take() else {
+                return;
+            };
+            let Some((_, target)) = &mut app.active_collection else {
+                return;
+            };
+            let result = coll_merge::execute(
+                target,
+                &root,
+                source,
+                &source_root,
+                &plan,
+                win.outside,
+                subfolder,
+                &mut app.database.uid_counter,
+            );
+            state
+                .thumbs_view
+                .update_from_collection(target, &state.filter);
+            state.thumbs_view.clamp_bottom(rw);
+            let outcome = match result {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    egui_state.modal.err(format!("{e}"));
+                    return;
+                }
+            };
+            let wants_remove = win.outside == OutsideFiles::Move && win.remove_after_move;
+            // Removing the merged collection would lose the tags of the entries left behind
+            let remove = wants_remove && outcome.left_behind == 0;
+            // The merged collection has entries for files that aren't there anymore
+            if !outcome.moved.is_empty()
+                && let Err(e) = update_merged(app, source_id, &outcome.moved, remove)
+            {
+                egui_state.modal.err(format!(
+                    "Merged, but failed to update the merged collection: {e:?}"
+                ));
+                return;
+            }
+            let mut problems = Vec::new();
+            if wants_remove && !remove {
+                problems.push(format!(
+                    "{} entries weren't moved, so the merged collection was kept",
+                    outcome.left_behind
+                ));
+            }
+            if !outcome.errors.is_empty() {
+                problems.push(format!(
+                    "Some files couldn't be copied or moved:\n{}",
+                    outcome.errors.join("\n")
+                ));
+            }
+            if problems.is_empty() {
+                egui_state.modal.success("Collections merged");
+            } else {
+                egui_state
+                    .modal
+                    .err(format!("Merged, but:\n{}", problems.join("\n")));
+            }
+        }
+    }
+}
+
+/// Remove the merged collection, or drop the entries of its moved files
+fn update_merged(
+    app: &mut Application,
+    id: collection::Id,
+    moved: &[PathBuf],
+    remove: bool,
+) -> anyhow::Result<()> {
+    if remove {
+        return app.remove_collection(id);
+    }
+    let mut merged = app.read_collection(id)?;
+    coll_merge::forget_moved(&mut merged, moved);
+    app.save_collection(id, &merged)
+}
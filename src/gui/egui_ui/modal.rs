@@ -12,6 +12,7 @@ pub enum PromptAction {
     QuitNoSave,
     DeleteTags(Vec<tag::Id>),
     MergeTag { merge: tag::Id, into: tag::Id },
+    CompactUids,
     PanicTest,
 }
 
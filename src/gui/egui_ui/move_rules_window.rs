@@ -0,0 +1,186 @@
+use {
+    super::{icons, EguiState},
+    crate::{
+        collection::Collection,
+        filter_reqs,
+        gui::State,
+        move_rules::{self, CollisionPolicy, MovePlan, MoveRule},
+    },
+    constcat::concat,
+    egui_sfml::{
+        egui::{self, Color32, ComboBox, Grid, RichText, ScrollArea, TextEdit},
+        sfml::graphics::RenderWindow,
+    },
+    std::path::PathBuf,
+};
+
+/// Edits the move rules of the collection, and runs them after showing a preview
+#[derive(Default)]
+pub struct MoveRulesWindow {
+    pub open: bool,
+    collisions: CollisionPolicy,
+    /// Dry run result, shown until applied or discarded
+    plan: Option<MovePlan>,
+}
+
+enum Action {
+    Preview,
+    Apply,
+    Undo,
+}
+
+pub(super) fn do_frame(
+    state: &mut State,
+    egui_state: &mut EguiState,
+    coll: &mut Collection,
+    egui_ctx: &egui::Context,
+    rw: &RenderWindow,
+) {
+    let win = &mut egui_state.move_rules_window;
+    if !win.open {
+        return;
+    }
+    let mut action = None;
+    egui::Window::new("Move rules")
+        .open(&mut win.open)
+        .show(egui_ctx, |ui| {
+            ui.label(
+                "Entries matching a query get moved into the folder of the first matching rule",
+            );
+            let mut remove = None;
+            Grid::new("move_rules_grid")
+                .num_columns(4)
+                .striped(true)
+                .show(ui, |ui| {
+                    for (i, rule) in coll.move_rules.iter_mut().enumerate() {
+                        ui.checkbox(&mut rule.enabled, "");
+                        ui.add(TextEdit::singleline(&mut rule.query).hint_text("Query"));
+                        filter_reqs::lowercase_tags(&mut rule.query);
+                        let mut folder = rule.folder.to_string_lossy().into_owned();
+                        if ui
+                            .add(TextEdit::singleline(&mut folder).hint_text("Folder"))
+                            .changed()
+                        {
+                            rule.folder = PathBuf::from(folder);
+                        }
+                        if ui.button(icons::REMOVE).on_hover_text("Remove").clicked() {
+                            remove = Some(i);
+                        }
+                        ui.end_row();
+                    }
+                });
+            if let Some(idx) = remove {
+                coll.move_rules.remove(idx);
+            }
+            ui.horizontal(|ui| {
+                if ui.button(concat!(icons::ADD, " Add rule")).clicked() {
+                    coll.move_rules.push(MoveRule {
+                        enabled: true,
+                        ..Default::default()
+                    });
+                }
+                ComboBox::new("move_rules_collisions", "On collision")
+                    .selected_text(win.collisions.label())
+                    .show_ui(ui, |ui| {
+                        for policy in CollisionPolicy::ALL {
+                            ui.selectable_value(&mut win.collisions, policy, policy.label());
+                        }
+                    });
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui
+                    .button("Preview")
+                    .on_hover_text("Dry run, nothing is moved")
+                    .clicked()
+                {
+                    action = Some(Action::Preview);
+                }
+                if ui
+                    .add_enabled(
+                        win.plan.as_ref().is_some_and(|plan| !plan.moves.is_empty()),
+                        egui::Button::new(concat!(icons::CHECK, " Apply")),
+                    )
+                    .clicked()
+                {
+                    action = Some(Action::Apply);
+                }
+                if ui
+                    .add_enabled(
+                        !coll.move_journal.is_empty(),
+                        egui::Button::new("⟲ Undo last run"),
+                    )
+                    .on_hover_text(format!("{} runs in the journal", coll.move_journal.len()))
+                    .clicked()
+                {
+                    action = Some(Action::Undo);
+                }
+            });
+            let Some(plan) = &win.plan else {
+                return;
+            };
+            ui.label(format!(
+                "{} moves, {} collisions",
+                plan.moves.len(),
+                plan.collisions.len()
+            ));
+            ScrollArea::vertical().max_height(480.0).show(ui, |ui| {
+                for mv in &plan.moves {
+                    ui.label(format!("{} → {}", mv.from.display(), mv.to.display()));
+                }
+                for mv in &plan.collisions {
+                    ui.label(
+                        RichText::new(format!(
+                            "{} → {} (taken, skipped)",
+                            mv.from.display(),
+                            mv.to.display()
+                        ))
+                        .color(Color32::YELLOW),
+                    );
+                }
+            });
+        });
+    let Some(action) = action else {
+        return;
+    };
+    let errors = match action {
+        Action::Preview => {
+            match move_rules::plan(coll, win.collisions) {
+                Ok(plan) => win.plan = Some(plan),
+                Err(e) => {
+                    win.plan = None;
+                    egui_state.modal.err(format!("{e}"));
+                }
+            }
+            return;
+        }
+        Action::Apply => {
+            let Some(plan) = win.plan.take() else {
+                return;
+            };
+            move_rules::execute(coll, &plan)
+        }
+        Action::Undo => {
+            win.plan = None;
+            match move_rules::undo_last(coll) {
+                Ok(errors) => errors,
+                Err(e) => {
+                    egui_state.modal.err(format!("{e}"));
+                    return;
+                }
+            }
+        }
+    };
+    state
+        .thumbs_view
+        .update_from_collection(coll, &state.filter);
+    state.thumbs_view.clamp_bottom(rw);
+    if errors.is_empty() {
+        egui_state.modal.success("Files moved");
+    } else {
+        egui_state.modal.err(format!(
+            "Some files couldn't be moved:\n{}",
+            errors.join("\n")
+        ));
+    }
+}
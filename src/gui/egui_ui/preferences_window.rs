@@ -3,7 +3,8 @@ use {
     crate::{
         gui::State,
         preferences::{
-            App, AppId, ScrollWheelMultiplier, ThumbnailsPerRow, UpDownArrowScrollSpeed, ValuePref,
+            App, AppId, ScrollWheelMultiplier, ThumbnailCacheBudget, ThumbnailLayout,
+            ThumbnailThreads, ThumbnailsPerRow, UpDownArrowScrollSpeed, ValuePref,
         },
     },
     constcat::concat,
@@ -300,6 +301,30 @@ fn ui_categ_ui(
     if slider_with_default::<ThumbnailsPerRow>(ui, &mut prefs.thumbs_per_row) {
         state.thumbs_view.resize(rw.size().x, prefs);
     }
+    ui.horizontal(|ui| {
+        ui.label("Layout");
+        for layout in ThumbnailLayout::ALL {
+            if ui
+                .radio_value(&mut prefs.thumbnail_layout, layout, layout.label())
+                .clicked()
+            {
+                state.thumbs_view.resize(rw.size().x, prefs);
+                state.thumbs_view.clamp_bottom(rw);
+            }
+        }
+    });
+    if slider_with_default::<ThumbnailThreads>(ui, &mut prefs.thumbnail_threads) {
+        state
+            .thumbnail_loader
+            .set_n_workers(prefs.thumbnail_threads.into());
+    }
+    if slider_with_default::<ThumbnailCacheBudget>(ui, &mut prefs.thumbnail_cache_mib) {
+        state.thumbnail_cache.set_budget(prefs.thumbnail_cache_mib);
+    }
+    ui.checkbox(
+        &mut prefs.animate_thumbnails_on_hover,
+        "Play animations when hovering over thumbnails",
+    );
     ui.heading("Scrolling");
     slider_with_default::<ScrollWheelMultiplier>(ui, &mut prefs.scroll_wheel_multiplier);
     slider_with_default::<UpDownArrowScrollSpeed>(ui, &mut prefs.arrow_key_scroll_speed);
@@ -323,6 +348,12 @@ fn ui_categ_ui(
         &mut prefs.use_built_in_viewer,
         "Use built-in viewer for supported formats",
     );
+    ui.separator();
+    ui.heading("Folder watching");
+    ui.checkbox(
+        &mut prefs.watch_folder,
+        "Watch the collection folder for changes while running",
+    );
 }
 
 /// Returns whether the value changes
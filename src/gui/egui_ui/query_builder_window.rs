@@ -0,0 +1,201 @@
+use {
+    super::{icons, EguiState},
+    crate::{
+        collection::Collection,
+        filter_reqs::{GroupKind, Req, Requirements},
+        gui::State,
+    },
+    egui_sfml::{
+        egui::{self, ComboBox, Frame, Key, TextEdit, Ui},
+        sfml::graphics::RenderWindow,
+    },
+};
+
+/// Edits the filter as a tree of requirements, kept in sync with the filter popup text
+#[derive(Default)]
+pub struct QueryBuilderWindow {
+    pub open: bool,
+    /// Name of the tag to add to a group
+    tag_buf: String,
+}
+
+/// Indices leading to a requirement, starting from the top level
+type ReqPath = Vec<usize>;
+
+enum Action {
+    Remove(ReqPath),
+    Move { from: ReqPath, to: ReqPath },
+    ToggleNot(ReqPath),
+    SetKind(ReqPath, GroupKind),
+    AddGroup(ReqPath),
+    AddTag(ReqPath),
+}
+
+pub(super) fn do_frame(
+    state: &mut State,
+    egui_state: &mut EguiState,
+    coll: &Collection,
+    egui_ctx: &egui::Context,
+    rw: &RenderWindow,
+) {
+    let win = &mut egui_state.query_builder_window;
+    if !win.open {
+        return;
+    }
+    let mut action = None;
+    egui::Window::new("Query builder")
+        .open(&mut win.open)
+        .show(egui_ctx, |ui| {
+            ui.label("Drag requirements by their handle to move them into a group");
+            let (_, payload) = ui.dnd_drop_zone::<ReqPath, ()>(Frame::group(ui.style()), |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Top level (all must match)");
+                    add_menu(ui, &mut win.tag_buf, &[], &mut action);
+                });
+                if state.filter.is_empty() {
+                    ui.label("Empty filter, everything matches");
+                }
+                for (i, req) in state.filter.iter().enumerate() {
+                    node_ui(ui, req, vec![i], coll, &mut win.tag_buf, &mut action);
+                }
+            });
+            if let Some(from) = payload {
+                action = Some(Action::Move {
+                    from: from.to_vec(),
+                    to: Vec::new(),
+                });
+            }
+        });
+    let Some(action) = action else {
+        return;
+    };
+    let filter = &mut state.filter;
+    match action {
+        Action::Remove(path) => {
+            filter.take_at(&path);
+        }
+        Action::Move { from, to } => {
+            if !filter.move_into(&from, &to) {
+                return;
+            }
+        }
+        Action::ToggleNot(path) => filter.toggle_not_at(&path),
+        Action::SetKind(path, kind) => filter.set_group_kind_at(&path, kind),
+        Action::AddGroup(path) => {
+            if let Some(group) = filter.group_at_mut(&path) {
+                group.push(GroupKind::All.wrap(Requirements::default()));
+            }
+        }
+        Action::AddTag(path) => {
+            let name = std::mem::take(&mut egui_state.query_builder_window.tag_buf);
+            let Some(id) = coll.resolve_tag(&name) else {
+                egui_state.modal.err(format!("No such tag: {name}"));
+                return;
+            };
+            if let Some(group) = filter.group_at_mut(&path) {
+                group.push(Req::Tag(id));
+            }
+        }
+    }
+    egui_state.filter_popup.string = state.filter.to_string(&coll.tags);
+    egui_state.filter_popup.clear_err();
+    state.wipe_search();
+    state
+        .thumbs_view
+        .update_from_collection(coll, &state.filter);
+    state.thumbs_view.clamp_bottom(rw);
+}
+
+fn node_ui(
+    ui: &mut Ui,
+    req: &Req,
+    path: ReqPath,
+    coll: &Collection,
+    tag_buf: &mut String,
+    action: &mut Option<Action>,
+) {
+    // Negations are shown as a toggle on the requirement they wrap
+    let mut negated = false;
+    let mut node = req;
+    while let Req::Not(inner) = node {
+        negated ^= true;
+        node = inner;
+    }
+    let group = match node {
+        Req::Any(reqs) => Some((GroupKind::Any, reqs)),
+        Req::All(reqs) => Some((GroupKind::All, reqs)),
+        Req::None(reqs) => Some((GroupKind::None, reqs)),
+        _ => None,
+    };
+    let Some((kind, reqs)) = group else {
+        ui.horizontal(|ui| {
+            node_controls(ui, negated, &path, action);
+            ui.label(node.to_string(&coll.tags));
+        });
+        return;
+    };
+    let (_, payload) = ui.dnd_drop_zone::<ReqPath, ()>(Frame::group(ui.style()), |ui| {
+        ui.horizontal(|ui| {
+            node_controls(ui, negated, &path, action);
+            let mut new_kind = kind;
+            ComboBox::from_id_salt(("query_builder_kind", &path))
+                .selected_text(kind.label())
+                .show_ui(ui, |ui| {
+                    for k in GroupKind::ALL {
+                        ui.selectable_value(&mut new_kind, k, k.label());
+                    }
+                });
+            if new_kind != kind {
+                *action = Some(Action::SetKind(path.clone(), new_kind));
+            }
+            add_menu(ui, tag_buf, &path, action);
+        });
+        for (i, child) in reqs.iter().enumerate() {
+            let mut child_path = path.clone();
+            child_path.push(i);
+            node_ui(ui, child, child_path, coll, tag_buf, action);
+        }
+    });
+    if let Some(from) = payload {
+        *action = Some(Action::Move {
+            from: from.to_vec(),
+            to: path,
+        });
+    }
+}
+
+/// Drag handle, negation toggle and remove button
+fn node_controls(ui: &mut Ui, negated: bool, path: &ReqPath, action: &mut Option<Action>) {
+    ui.dnd_drag_source(
+        egui::Id::new("query_builder_node").with(path),
+        path.clone(),
+        |ui| ui.label(icons::HAMBURGER),
+    );
+    if ui
+        .selectable_label(negated, "!")
+        .on_hover_text("Negate")
+        .clicked()
+    {
+        *action = Some(Action::ToggleNot(path.clone()));
+    }
+    if ui.button(icons::REMOVE).on_hover_text("Remove").clicked() {
+        *action = Some(Action::Remove(path.clone()));
+    }
+}
+
+fn add_menu(ui: &mut Ui, tag_buf: &mut String, path: &[usize], action: &mut Option<Action>) {
+    ui.menu_button(icons::ADD, |ui| {
+        ui.horizontal(|ui| {
+            let re = ui.add(TextEdit::singleline(tag_buf).hint_text("Tag name"));
+            let enter = re.lost_focus() && ui.input(|inp| inp.key_pressed(Key::Enter));
+            if (ui.button("Add tag").clicked() || enter) && !tag_buf.is_empty() {
+                *action = Some(Action::AddTag(path.to_vec()));
+                ui.close_menu();
+            }
+        });
+        if ui.button("Add group").clicked() {
+            *action = Some(Action::AddGroup(path.to_vec()));
+            ui.close_menu();
+        }
+    });
+}
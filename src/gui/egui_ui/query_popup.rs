@@ -1,6 +1,14 @@
 use {
     super::tag_autocomplete::AcState,
-    egui_sfml::egui::{Align2, Context, Ui, Window},
+    crate::{
+        collection::Collection,
+        filter_reqs::{ParseResolveError, ReqTransformError, NON_TAG_PARAM_FNS},
+    },
+    egui_sfml::egui::{
+        text::{LayoutJob, TextFormat},
+        Align2, Color32, Context, FontSelection, Stroke, Ui, Window,
+    },
+    std::ops::Range,
 };
 
 #[derive(Default)]
@@ -8,6 +16,8 @@ pub struct QueryPopup {
     pub on: bool,
     pub string: String,
     pub err_string: String,
+    /// Byte range of `string` that the error refers to
+    pub err_span: Option<Range<usize>>,
     pub ac_state: AcState,
 }
 
@@ -38,4 +48,122 @@ impl QueryPopup {
             });
         }
     }
+    pub fn clear_err(&mut self) {
+        self.err_string.clear();
+        self.err_span = None;
+    }
+}
+
+/// Error message and span for a failed query, with "did you mean" suggestions for unknown tags
+pub(super) fn describe_err(
+    err: &ParseResolveError,
+    src: &str,
+    coll: &Collection,
+) -> (String, Option<Range<usize>>) {
+    let mut msg = format!("Error: {err}");
+    if let ParseResolveError::ReqTransform(ReqTransformError::NoSuchTag(name)) = err {
+        let similar = coll.similar_tag_names(name, 3);
+        if !similar.is_empty() {
+            msg.push_str(&format!("\nDid you mean: {}?", similar.join(", ")));
+        }
+    }
+    (msg, err.span(src))
+}
+
+#[derive(Clone, Copy)]
+enum TokenKind {
+    Plain,
+    Negation,
+    Bracket,
+    Function,
+    Literal,
+    Tag,
+    UnknownTag,
+}
+
+/// Lay out query text with syntax highlighting, underlining `err_span`
+pub(super) fn highlight_query(
+    ui: &Ui,
+    text: &str,
+    coll: &Collection,
+    err_span: Option<&Range<usize>>,
+) -> LayoutJob {
+    let font_id = FontSelection::default().resolve(ui.style());
+    let plain_color = ui.visuals().text_color();
+    let mut job = LayoutJob::default();
+    // Function names of the brackets we're inside of
+    let mut fn_stack: Vec<&str> = Vec::new();
+    let mut last_fn = "";
+    let mut pos = 0;
+    loop {
+        let rest = &text[pos..];
+        let Some(c) = rest.chars().next() else {
+            break;
+        };
+        let len = match c {
+            '!' | '[' | ']' => c.len_utf8(),
+            c if c.is_whitespace() => rest
+                .find(|c: char| !c.is_whitespace())
+                .unwrap_or(rest.len()),
+            _ => rest
+                .find(|c: char| c.is_whitespace() || matches!(c, '!' | '[' | ']'))
+                .unwrap_or(rest.len()),
+        };
+        let token = &rest[..len];
+        let kind = match c {
+            '!' => TokenKind::Negation,
+            '[' => {
+                fn_stack.push(std::mem::take(&mut last_fn));
+                TokenKind::Bracket
+            }
+            ']' => {
+                fn_stack.pop();
+                TokenKind::Bracket
+            }
+            c if c.is_whitespace() => TokenKind::Plain,
+            '@' => {
+                last_fn = &token[1..];
+                TokenKind::Function
+            }
+            _ => {
+                if fn_stack
+                    .last()
+                    .is_some_and(|name| NON_TAG_PARAM_FNS.contains(name))
+                {
+                    TokenKind::Literal
+                } else if coll.resolve_tag(token).is_some() {
+                    TokenKind::Tag
+                } else {
+                    TokenKind::UnknownTag
+                }
+            }
+        };
+        let color = match kind {
+            TokenKind::Plain => plain_color,
+            TokenKind::Negation => Color32::ORANGE,
+            TokenKind::Bracket => Color32::GRAY,
+            TokenKind::Function => Color32::LIGHT_BLUE,
+            TokenKind::Literal => Color32::GOLD,
+            TokenKind::Tag => Color32::LIGHT_GREEN,
+            TokenKind::UnknownTag => Color32::RED,
+        };
+        let in_err = err_span.is_some_and(|span| span.start < pos + len && pos < span.end);
+        let underline = if in_err {
+            Stroke::new(1.5, Color32::RED)
+        } else {
+            Stroke::NONE
+        };
+        job.append(
+            token,
+            0.0,
+            TextFormat {
+                font_id: font_id.clone(),
+                color,
+                underline,
+                ..Default::default()
+            },
+        );
+        pos += len;
+    }
+    job
 }
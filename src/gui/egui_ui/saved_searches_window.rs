@@ -0,0 +1,149 @@
+use {
+    super::{icons, EguiState},
+    crate::{collection::Collection, filter_reqs, gui::State},
+    constcat::concat,
+    egui_sfml::{
+        egui::{self, Button, Grid, Key, TextEdit},
+        sfml::graphics::RenderWindow,
+    },
+};
+
+#[derive(Default)]
+pub struct SavedSearchesWindow {
+    pub open: bool,
+    new_name: String,
+    /// The saved search being renamed, along with the new name buffer
+    rename: Option<(String, String)>,
+}
+
+enum Action {
+    Apply(String),
+    Rename { old: String, new: String },
+    Delete(String),
+}
+
+pub(super) fn do_frame(
+    state: &mut State,
+    egui_state: &mut EguiState,
+    coll: &mut Collection,
+    egui_ctx: &egui::Context,
+    rw: &RenderWindow,
+) {
+    let win = &mut egui_state.saved_searches_window;
+    if !win.open {
+        return;
+    }
+    let mut action = None;
+    egui::Window::new("Saved searches")
+        .open(&mut win.open)
+        .show(egui_ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.add(TextEdit::singleline(&mut win.new_name).hint_text("Name"));
+                win.new_name.make_ascii_lowercase();
+                let can_save =
+                    !win.new_name.is_empty() && !coll.saved_searches.contains_key(&win.new_name);
+                if ui
+                    .add_enabled(
+                        can_save,
+                        Button::new(concat!(icons::SAVE, " Save current filter")),
+                    )
+                    .clicked()
+                {
+                    coll.saved_searches.insert(
+                        std::mem::take(&mut win.new_name),
+                        egui_state.filter_popup.string.clone(),
+                    );
+                }
+            });
+            ui.separator();
+            if coll.saved_searches.is_empty() {
+                ui.label("No saved searches yet");
+                return;
+            }
+            let mut names: Vec<String> = coll.saved_searches.keys().cloned().collect();
+            names.sort();
+            Grid::new("saved_searches_grid")
+                .num_columns(3)
+                .striped(true)
+                .show(ui, |ui| {
+                    for name in names {
+                        match &mut win.rename {
+                            Some((old, new)) if *old == name => {
+                                let re = ui.text_edit_singleline(new);
+                                new.make_ascii_lowercase();
+                                re.request_focus();
+                                if re.lost_focus() {
+                                    if ui.input(|inp| inp.key_pressed(Key::Enter)) {
+                                        action = Some(Action::Rename {
+                                            old: old.clone(),
+                                            new: new.clone(),
+                                        });
+                                    }
+                                    win.rename = None;
+                                }
+                            }
+                            _ => {
+                                ui.label(&name);
+                            }
+                        }
+                        if let Some(query) = coll.saved_searches.get_mut(&name) {
+                            ui.add(TextEdit::singleline(query).hint_text("Query"));
+                            filter_reqs::lowercase_tags(query);
+                        }
+                        ui.horizontal(|ui| {
+                            if ui
+                                .button(icons::CHECK)
+                                .on_hover_text("Apply as filter")
+                                .clicked()
+                            {
+                                action = Some(Action::Apply(name.clone()));
+                            }
+                            if ui.button(icons::EDIT).on_hover_text("Rename").clicked() {
+                                win.rename = Some((name.clone(), name.clone()));
+                            }
+                            if ui.button(icons::REMOVE).on_hover_text("Delete").clicked() {
+                                action = Some(Action::Delete(name.clone()));
+                            }
+                        });
+                        ui.end_row();
+                    }
+                });
+        });
+    let Some(action) = action else {
+        return;
+    };
+    match action {
+        Action::Apply(name) => {
+            let Some(query) = coll.saved_searches.get(&name) else {
+                egui_state
+                    .modal
+                    .err(format!("No such saved search: {name}"));
+                return;
+            };
+            match state.filter.parse_and_resolve(query, coll) {
+                Ok(()) => {
+                    egui_state.filter_popup.string.clone_from(query);
+                    egui_state.filter_popup.clear_err();
+                    state.wipe_search();
+                    state
+                        .thumbs_view
+                        .update_from_collection(coll, &state.filter);
+                    state.thumbs_view.clamp_bottom(rw);
+                }
+                Err(e) => {
+                    egui_state
+                        .modal
+                        .err(format!("Failed to apply saved search: {e}"));
+                }
+            }
+        }
+        Action::Rename { old, new } => {
+            if let Err(e) = coll.rename_saved_search(&old, &new) {
+                egui_state.modal.err(format!("Rename error: {e}"));
+            }
+        }
+        Action::Delete(name) => {
+            coll.saved_searches.remove(&name);
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use {
+    super::EguiState,
+    crate::{
+        collection::{self, Collection},
+        entry,
+        filter_reqs::{Req, SimilarTo},
+        gui::{open::builtin::open_list, State},
+        phash::{self, HashScan},
+    },
+    egui_sfml::{
+        egui::{
+            self, load::SizedTexture, Color32, Image, ImageButton, RichText, ScrollArea, Slider,
+            TextureId,
+        },
+        sfml::graphics::RenderWindow,
+    },
+    std::{path::Path, sync::atomic::Ordering},
+};
+
+/// Finds entries that look like a reference entry, using perceptual hashes
+pub struct SimilarWindow {
+    pub open: bool,
+    pub reference: Option<entry::Id>,
+    /// Maximum hash distance to count as similar
+    max_dist: u32,
+    scan: Option<HashScan>,
+    candidates: Option<Candidates>,
+}
+
+/// Entries similar to the reference, kept until the search changes
+struct Candidates {
+    /// Reference, its hash, `max_dist` and the number of hashes they were found with
+    key: (entry::Id, u64, u32, usize),
+    /// Entry and distance from the reference, closest first
+    list: Vec<(entry::Id, u32)>,
+}
+
+impl Default for SimilarWindow {
+    fn default() -> Self {
+        Self {
+            open: false,
+            reference: None,
+            max_dist: phash::DEFAULT_MAX_DIST,
+            scan: None,
+            candidates: None,
+        }
+    }
+}
+
+impl SimilarWindow {
+    pub fn find_similar_to(&mut self, id: entry::Id) {
+        self.open = true;
+        self.reference = Some(id);
+    }
+}
+
+enum Action {
+    HashMissing,
+    /// Open the viewer on the reference and the candidates, starting at this index
+    View(usize),
+    AddToFilter,
+}
+
+pub(super) fn do_frame(
+    state: &mut State,
+    egui_state: &mut EguiState,
+    coll: &mut Collection,
+    coll_id: collection::Id,
+    root: &Path,
+    egui_ctx: &egui::Context,
+    rw: &RenderWindow,
+) {
+    let win = &mut egui_state.similar_window;
+    if let Some(scan) = &mut win.scan
+        && let Some(result) = scan.poll()
+    {
+        let scan_coll = scan.coll_id;
+        win.scan = None;
+        match result {
+            Ok(hashes) if scan_coll == coll_id => coll.store_phashes(hashes),
+            Ok(_) => {}
+            Err(e) => egui_state.modal.err(format!("Hashing error: {e:?}")),
+        }
+    }
+    let win = &mut egui_state.similar_window;
+    if !win.open {
+        return;
+    }
+    // Hashes of entries that go away are dropped along with them
+    let n_hashed = coll.perceptual_hashes.len();
+    let reference = win
+        .reference
+        .filter(|id| coll.entries.contains_key(id))
+        .map(|id| {
+            (
+                id,
+                coll.perceptual_hashes.get(&id).map(|stored| stored.hash),
+            )
+        });
+    let candidates: &[(entry::Id, u32)] = match reference {
+        Some((ref_id, Some(hash))) => {
+            let key = (ref_id, hash, win.max_dist, n_hashed);
+            if win.candidates.as_ref().is_none_or(|cands| cands.key != key) {
+                win.candidates = Some(Candidates {
+                    key,
+                    list: coll
+                        .similar_entries(hash, win.max_dist)
+                        .into_iter()
+                        .filter(|(id, _)| *id != ref_id)
+                        .collect(),
+                });
+            }
+            win.candidates
+                .as_ref()
+                .map_or(&[][..], |cands| cands.list.as_slice())
+        }
+        _ => &[],
+    };
+    let mut action = None;
+    egui::Window::new("Similar images")
+        .open(&mut win.open)
+        .show(egui_ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("{n_hashed}/{} entries hashed", coll.entries.len()));
+                if ui
+                    .add_enabled(
+                        win.scan.is_none() && n_hashed < coll.entries.len(),
+                        egui::Button::new("Hash missing"),
+                    )
+                    .on_hover_text("Entries also get hashed as their thumbnails load")
+                    .clicked()
+                {
+                    action = Some(Action::HashMissing);
+                }
+                if let Some(scan) = &win.scan {
+                    ui.spinner();
+                    ui.label(format!(
+                        "{}/{}",
+                        scan.hashed.load(Ordering::Relaxed),
+                        scan.total
+                    ));
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Use first selected entry").clicked()
+                    && let Some(&id) = state.sel.current().and_then(|buf| buf.as_vec().first())
+                {
+                    win.reference = Some(id);
+                }
+                ui.add(Slider::new(&mut win.max_dist, 0..=32).text("Max distance"))
+                    .on_hover_text("How many bits of the 64 bit hashes can differ");
+            });
+            ui.separator();
+            let Some((ref_id, ref_hash)) = reference else {
+                ui.label("No reference entry chosen");
+                return;
+            };
+            ui.horizontal(|ui| {
+                ui.add(Image::new(SizedTexture::new(
+                    TextureId::User(ref_id.0),
+                    (128., 128.),
+                )));
+                ui.vertical(|ui| {
+                    ui.label(coll.entries[&ref_id].path.to_string_lossy());
+                    if ref_hash.is_none() {
+                        ui.label(
+                            RichText::new("Not hashed yet, use \"Hash missing\"")
+                                .color(Color32::YELLOW),
+                        );
+                    } else if ui
+                        .button("Add to filter")
+                        .on_hover_text("Add a @similar requirement for this entry to the filter")
+                        .clicked()
+                    {
+                        action = Some(Action::AddToFilter);
+                    }
+                });
+            });
+            ui.separator();
+            if ref_hash.is_some() && candidates.is_empty() {
+                ui.label("No similar images found");
+            }
+            ScrollArea::vertical().max_height(640.0).show(ui, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    for (i, (id, dist)) in candidates.iter().enumerate() {
+                        ui.vertical(|ui| {
+                            let re = ui
+                                .add(ImageButton::new(SizedTexture::new(
+                                    TextureId::User(id.0),
+                                    (128.0, 128.0),
+                                )))
+                                .on_hover_text(coll.entries[id].path.to_string_lossy());
+                            if re.clicked() {
+                                // The reference comes first in the list
+                                action = Some(Action::View(i + 1));
+                            }
+                            ui.label(format!("distance {dist}"));
+                        });
+                    }
+                });
+            });
+        });
+    let Some(action) = action else {
+        return;
+    };
+    match action {
+        Action::HashMissing => {
+            let files = coll
+                .entries
+                .iter()
+                .filter(|(id, _)| !coll.perceptual_hashes.contains_key(id))
+                .map(|(id, en)| (*id, root.join(&en.path)))
+                .collect();
+            win.scan = Some(HashScan::start(coll_id, files));
+        }
+        Action::View(idx) => {
+            let Some((ref_id, _)) = reference else {
+                return;
+            };
+            let list = std::iter::once(ref_id)
+                .chain(candidates.iter().map(|(id, _)| *id))
+                .collect();
+            open_list(state, list, idx, rw);
+        }
+        Action::AddToFilter => {
+            let Some((ref_id, _)) = reference else {
+                return;
+            };
+            state.filter.push(Req::Similar {
+                to: SimilarTo::Entry(ref_id),
+                max_dist: win.max_dist,
+                matches: std::iter::once(ref_id)
+                    .chain(candidates.iter().map(|(id, _)| *id))
+                    .collect(),
+            });
+            egui_state.filter_popup.string = state.filter.to_string(&coll.tags);
+            egui_state.filter_popup.clear_err();
+            state.wipe_search();
+            state
+                .thumbs_view
+                .update_from_collection(coll, &state.filter);
+            state.thumbs_view.clamp_bottom(rw);
+        }
+    }
+}
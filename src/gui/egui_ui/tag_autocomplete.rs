@@ -110,9 +110,25 @@ pub(super) fn tag_autocomplete_popup(
             ("@untagged", "Entries that don't have any tags", "@untagged"),
             (
                 "@ntags[n]",
-                "The entry must have this many tags",
+                "The entry must have this many tags (also >n, <=n, n..m)",
                 "@ntags[]",
             ),
+            (
+                "@implied[tag]",
+                "Entries that only have the tag through implication",
+                "@implied[]",
+            ),
+            ("@saved[name]", "A saved search", "@saved[]"),
+            (
+                "@similar[path dist]",
+                "Images that look like the one at path (dist is optional)",
+                "@similar[]",
+            ),
+            (
+                "@similar-id[uid dist]",
+                "Images that look like the entry with this uid (dist is optional)",
+                "@similar-id[]",
+            ),
         ];
         let last_is_special = last.bytes().next() == Some(b'@');
         if last_is_special {
@@ -240,15 +240,9 @@ fn file_menu(
         }
         if ui.button("↺ Reload folder").clicked() {
             ui.close_menu();
-            let changes = match app.reload_active_collection() {
-                Ok(changes) => changes,
-                Err(e) => {
-                    *result = Err(e);
-                    return;
-                }
-            };
-            if !changes.empty() {
-                egui_state.changes_window.open(changes);
+            match app.reload_active_collection() {
+                Ok(scan) => egui_state.changes_window.start_scan(scan),
+                Err(e) => *result = Err(e),
             }
         }
         if ui
@@ -291,10 +285,8 @@ fn file_menu(
                 }
                 match action {
                     Action::Open(id) => match app.load_collection(id) {
-                        Ok(changes) => {
-                            if !changes.empty() {
-                                egui_state.changes_window.open(changes);
-                            }
+                        Ok(scan) => {
+                            egui_state.changes_window.start_scan(scan);
                             *result = crate::gui::set_active_collection(
                                 &mut state.thumbs_view,
                                 app,
@@ -332,6 +324,16 @@ fn file_menu(
             egui_state.file_dialog.select_file();
             egui_state.file_op = Some(FileOp::RestoreBackup);
         }
+        if ui.button("🗜 Compact Uids").clicked() {
+            ui.close_menu();
+            egui_state.modal.prompt(
+                "Compact Uids",
+                "Renumber the entries, tags, sequences and collections of every collection, \
+                 closing the gaps left by removed ones.\n\
+                 A backup of everything is saved in the data folder first.",
+                PromptAction::CompactUids,
+            );
+        }
         ui.separator();
         if ui
             .button(concat!(icons::HAMBURGER, " Preferences"))
@@ -382,6 +384,54 @@ fn collection_menu(ui: &mut egui::Ui, egui_state: &mut EguiState) {
             ui.close_menu();
             egui_state.changes_window.open ^= true;
         }
+        if ui
+            .button(concat!(icons::SEARCH, " Saved searches"))
+            .clicked()
+        {
+            ui.close_menu();
+            egui_state.saved_searches_window.open ^= true;
+        }
+        if ui
+            .button(concat!(icons::WRENCH, " Query builder"))
+            .clicked()
+        {
+            ui.close_menu();
+            egui_state.query_builder_window.open ^= true;
+        }
+        if ui
+            .button(concat!(icons::DUPLICATE, " Find duplicates"))
+            .clicked()
+        {
+            ui.close_menu();
+            egui_state.duplicates_window.open ^= true;
+        }
+        if ui
+            .button(concat!(icons::SIMILAR, " Similar images"))
+            .clicked()
+        {
+            ui.close_menu();
+            egui_state.similar_window.open ^= true;
+        }
+        if ui.button(concat!(icons::MOVE, " Move rules")).clicked() {
+            ui.close_menu();
+            egui_state.move_rules_window.open ^= true;
+        }
+        if ui.button("📥 Import files").clicked() {
+            ui.close_menu();
+            egui_state.import_window.open ^= true;
+        }
+        if ui.button("⛙ Merge collection into this").clicked() {
+            ui.close_menu();
+            egui_state.merge_window.open ^= true;
+        }
+        if ui.button("⛗ Extract into new collection").clicked() {
+            ui.close_menu();
+            egui_state.extract_window.open ^= true;
+        }
+        if ui.button("🩺 Check collection").clicked() {
+            ui.close_menu();
+            egui_state.fsck_window.open ^= true;
+        }
         if ui
             .button(concat!(icons::HAMBURGER, " Preferences"))
             .clicked()
@@ -11,7 +11,7 @@ use {
 /// Open functionality when enter is pressed in thumbnails view
 pub(in crate::gui) fn on_enter_open(state: &mut State, window: &RenderWindow) {
     if state.sel.none_selected() {
-        open_list(state, state.thumbs_view.uids.clone(), 0, window);
+        open_list(state, state.thumbs_view.uids().to_vec(), 0, window);
     } else if let Some(id_vec) = state.sel.current_as_nonempty_id_vec() {
         open_list(state, id_vec.clone(), 0, window);
     }
@@ -49,7 +49,12 @@ pub(in crate::gui) fn open_single_with_others(
         };
         open_list(state, image_list, 0, window);
     } else {
-        open_list(state, state.thumbs_view.uids.clone(), thumb_index, window);
+        open_list(
+            state,
+            state.thumbs_view.uids().to_vec(),
+            thumb_index,
+            window,
+        );
     };
     Ok(())
 }
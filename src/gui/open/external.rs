@@ -1,9 +1,10 @@
 use {
     crate::{
+        archive,
         collection::{Collection, Entries},
         entry,
         gui::State,
-        preferences::{AppId, Preferences},
+        preferences::{App, AppId, Preferences},
         sequence::Sequence,
     },
     anyhow::{bail, Context},
@@ -67,13 +68,10 @@ pub fn open(
     candidates: &[OpenExternCandidate],
     preferences: &mut Preferences,
 ) -> anyhow::Result<()> {
+    refuse_archive_members(candidates.iter().map(|c| c.path))?;
     let built_tasks = build_tasks(candidates, preferences);
     for task in built_tasks.tasks {
-        let app = &preferences.applications[&task.app];
-        let mut cmd = Command::new(&app.path);
-        feed_args(&app.args_string, &task.args, &mut cmd);
-        cmd.spawn()
-            .with_context(|| format!("Failed to spawn command {:?}", app.path))?;
+        open_with_app(&preferences.applications[&task.app], &task.args)?;
     }
     if built_tasks.remainder.len() >= 5 {
         let msg = "\
@@ -172,6 +170,27 @@ pub(crate) fn open_sequence(
     open(&candidates, prefs)
 }
 
+/// Open `paths` with a specific app, regardless of associations
+pub fn open_with_app(app: &App, paths: &[&Path]) -> anyhow::Result<()> {
+    refuse_archive_members(paths.iter().copied())?;
+    let mut cmd = Command::new(&app.path);
+    feed_args(&app.args_string, paths, &mut cmd);
+    cmd.spawn()
+        .with_context(|| format!("Failed to spawn command {:?}", app.path))?;
+    Ok(())
+}
+
+/// External programs can't open the virtual paths of images inside archives
+fn refuse_archive_members<'p>(mut paths: impl Iterator<Item = &'p Path>) -> anyhow::Result<()> {
+    if let Some(path) = paths.find(|path| archive::split_member(path).is_some()) {
+        bail!(
+            "{} is inside an archive, only the built-in viewer can open it",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
 pub fn feed_args(args_string: &str, paths: &[&Path], command: &mut Command) {
     if args_string.is_empty() {
         command.args(paths);
@@ -1,4 +1,5 @@
 use {
+    crate::file_type::FileType,
     anyhow::Context,
     egui_sfml::sfml::{
         cpp::FBox,
@@ -16,9 +17,20 @@ pub struct Resources {
     pub loading_texture: FBox<Texture>,
     pub error_texture: FBox<Texture>,
     pub sel_begin_texture: FBox<Texture>,
+    /// Placeholder thumbnails for files that aren't images
+    pub type_textures: TypeTextures,
     pub font: FBox<Font>,
 }
 
+pub struct TypeTextures {
+    pub pdf: FBox<Texture>,
+    pub video: FBox<Texture>,
+    pub audio: FBox<Texture>,
+    pub text: FBox<Texture>,
+    pub archive: FBox<Texture>,
+    pub other: FBox<Texture>,
+}
+
 impl Resources {
     pub fn load() -> anyhow::Result<Self> {
         let mut loading_texture = Texture::new().context("texture create error")?;
@@ -31,11 +43,41 @@ impl Resources {
         error_texture.load_from_memory(include_bytes!(res!("error.png")), IntRect::default())?;
         sel_begin_texture
             .load_from_memory(include_bytes!(res!("select_begin.png")), IntRect::default())?;
+        let type_textures = TypeTextures {
+            pdf: load_texture(include_bytes!(res!("type_pdf.png")))?,
+            video: load_texture(include_bytes!(res!("type_video.png")))?,
+            audio: load_texture(include_bytes!(res!("type_audio.png")))?,
+            text: load_texture(include_bytes!(res!("type_text.png")))?,
+            archive: load_texture(include_bytes!(res!("type_archive.png")))?,
+            other: load_texture(include_bytes!(res!("type_other.png")))?,
+        };
         Ok(Self {
             loading_texture,
             error_texture,
             sel_begin_texture,
+            type_textures,
             font,
         })
     }
+    /// Placeholder thumbnail for a file of type `ft`.
+    ///
+    /// Images only get here if they failed to load, so they get the error texture.
+    pub fn type_texture(&self, ft: FileType) -> &Texture {
+        let tt = &self.type_textures;
+        match ft {
+            FileType::Image => &self.error_texture,
+            FileType::Pdf => &tt.pdf,
+            FileType::Video => &tt.video,
+            FileType::Audio => &tt.audio,
+            FileType::Text => &tt.text,
+            FileType::Archive => &tt.archive,
+            FileType::Other => &tt.other,
+        }
+    }
+}
+
+fn load_texture(data: &[u8]) -> anyhow::Result<FBox<Texture>> {
+    let mut tex = Texture::new().context("texture create error")?;
+    tex.load_from_memory(data, IntRect::default())?;
+    Ok(tex)
 }
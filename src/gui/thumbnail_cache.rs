@@ -0,0 +1,180 @@
+use {
+    super::edit_watch,
+    crate::{archive, collection::Entries, db::EntryMap, entry, file_type::FileType},
+    egui_sfml::sfml::{cpp::FBox, graphics::Texture},
+    std::{
+        cell::Cell,
+        time::{Instant, SystemTime},
+    },
+};
+
+/// Loaded thumbnail textures, or the file type of the ones that couldn't be loaded as images.
+///
+/// When the textures take up more memory than the budget, the least recently used ones are
+/// evicted. Textures used in the current or the previous frame are never evicted, so the
+/// visible thumbnails (and the prefetched ones around them) stay.
+pub struct ThumbnailCache {
+    slots: EntryMap<Slot>,
+    /// Bytes taken up by the textures
+    used: usize,
+    budget: usize,
+    /// Increases with every frame
+    frame: u64,
+    /// Width / height of the loaded thumbnails. Kept after eviction, for laying out thumbnails.
+    aspect_ratios: EntryMap<f32>,
+    /// Increases when `aspect_ratios` changes
+    aspect_gen: u64,
+    last_stale_check: Instant,
+}
+
+struct Slot {
+    tex: Result<FBox<Texture>, FileType>,
+    /// The last frame this was used in
+    last_used: Cell<u64>,
+    /// Modification time of the file when it was loaded
+    mtime: Option<SystemTime>,
+}
+
+impl ThumbnailCache {
+    pub fn new(budget_mib: u16) -> Self {
+        Self {
+            slots: EntryMap::default(),
+            used: 0,
+            budget: mib_to_bytes(budget_mib),
+            frame: 0,
+            aspect_ratios: EntryMap::default(),
+            aspect_gen: 0,
+            last_stale_check: Instant::now(),
+        }
+    }
+    /// Should be called once per frame, before using the cache for the frame
+    pub fn begin_frame(&mut self) {
+        self.frame += 1;
+    }
+    /// `None` if not loaded yet, `Some(Err(file_type))` if it couldn't be loaded as an image
+    pub fn get(&self, id: &entry::Id) -> Option<Result<&Texture, FileType>> {
+        let slot = self.slots.get(id)?;
+        slot.last_used.set(self.frame);
+        Some(slot.tex.as_deref().map_err(|ft| *ft))
+    }
+    pub fn insert(
+        &mut self,
+        id: entry::Id,
+        tex: Result<FBox<Texture>, FileType>,
+        mtime: Option<SystemTime>,
+    ) {
+        if let Ok(tex) = &tex {
+            let size = tex.size();
+            let aspect = size.x as f32 / size.y.max(1) as f32;
+            if self.aspect_ratios.insert(id, aspect) != Some(aspect) {
+                self.aspect_gen += 1;
+            }
+        }
+        self.used += tex.as_deref().map_or(0, tex_bytes);
+        let slot = Slot {
+            tex,
+            last_used: Cell::new(self.frame),
+            mtime,
+        };
+        if let Some(old) = self.slots.insert(id, slot) {
+            self.used -= old.tex.as_deref().map_or(0, tex_bytes);
+        }
+        if self.used > self.budget {
+            self.evict();
+        }
+    }
+    pub fn remove(&mut self, id: &entry::Id) {
+        if let Some(slot) = self.slots.remove(id) {
+            self.used -= slot.tex.as_deref().map_or(0, tex_bytes);
+        }
+    }
+    /// Remove the thumbnails of `ids` whose file changed since they were loaded, so they get
+    /// loaded again.
+    ///
+    /// Only checks every [`edit_watch::CHECK_INTERVAL`]. Returns the removed ids.
+    pub fn remove_stale(
+        &mut self,
+        ids: impl Iterator<Item = entry::Id>,
+        entries: &Entries,
+    ) -> Vec<entry::Id> {
+        if self.last_stale_check.elapsed() < edit_watch::CHECK_INTERVAL {
+            return Vec::new();
+        }
+        self.last_stale_check = Instant::now();
+        let stale: Vec<entry::Id> = ids
+            .filter(|id| {
+                let (Some(slot), Some(en)) = (self.slots.get(id), entries.get(id)) else {
+                    return false;
+                };
+                slot.mtime.is_some() && archive::mtime(&en.path) != slot.mtime
+            })
+            .collect();
+        for id in &stale {
+            self.remove(id);
+        }
+        stale
+    }
+    pub fn set_budget(&mut self, budget_mib: u16) {
+        self.budget = mib_to_bytes(budget_mib);
+        if self.used > self.budget {
+            self.evict();
+        }
+    }
+    fn evict(&mut self) {
+        let protected_since = self.frame.saturating_sub(1);
+        let mut candidates: Vec<(entry::Id, u64)> = self
+            .slots
+            .iter()
+            .filter(|(_, slot)| slot.last_used.get() < protected_since)
+            .map(|(id, slot)| (*id, slot.last_used.get()))
+            .collect();
+        candidates.sort_unstable_by_key(|(_, last_used)| *last_used);
+        for (id, _) in candidates {
+            if self.used <= self.budget {
+                break;
+            }
+            self.remove(&id);
+        }
+    }
+    /// Replace the old entry ids with the new ones. Ids not in `map` are forgotten.
+    pub fn remap_entry_ids(&mut self, map: &EntryMap<entry::Id>) {
+        self.slots = std::mem::take(&mut self.slots)
+            .into_iter()
+            .filter_map(|(id, slot)| Some((*map.get(&id)?, slot)))
+            .collect();
+        self.used = self
+            .slots
+            .values()
+            .map(|slot| slot.tex.as_deref().map_or(0, tex_bytes))
+            .sum();
+        self.aspect_ratios = std::mem::take(&mut self.aspect_ratios)
+            .into_iter()
+            .filter_map(|(id, aspect)| Some((*map.get(&id)?, aspect)))
+            .collect();
+        self.aspect_gen += 1;
+    }
+    pub fn aspect_ratio(&self, id: &entry::Id) -> Option<f32> {
+        self.aspect_ratios.get(id).copied()
+    }
+    pub fn aspect_gen(&self) -> u64 {
+        self.aspect_gen
+    }
+    pub fn n_slots(&self) -> usize {
+        self.slots.len()
+    }
+    pub fn used_bytes(&self) -> usize {
+        self.used
+    }
+    pub fn budget_bytes(&self) -> usize {
+        self.budget
+    }
+}
+
+fn tex_bytes(tex: &Texture) -> usize {
+    let size = tex.size();
+    size.x as usize * size.y as usize * 4
+}
+
+fn mib_to_bytes(mib: u16) -> usize {
+    usize::from(mib) * 1024 * 1024
+}
@@ -1,66 +1,196 @@
 use {
-    crate::{db::EntryMap, entry, gui::ThumbnailCache},
+    crate::{
+        archive,
+        db::{EntryMap, EntrySet},
+        entry, exif_meta,
+        file_type::{self, FileType},
+        gui::{edit_watch, ThumbnailCache},
+        phash::{self, StoredHash},
+    },
     egui_sfml::sfml::{cpp::FBox, graphics::Texture},
-    image::{imageops::FilterType, ImageBuffer, ImageResult, Rgba},
-    parking_lot::Mutex,
-    std::{collections::hash_map, path::Path, sync::Arc},
+    image::{imageops::FilterType, ImageBuffer, Rgba},
+    parking_lot::{Condvar, Mutex},
+    std::{
+        cmp::Reverse,
+        path::{Path, PathBuf},
+        sync::Arc,
+        time::SystemTime,
+    },
 };
 
 type RgbaBuf = ImageBuffer<Rgba<u8>, Vec<u8>>;
-type ImageSlot = Option<ImageResult<RgbaBuf>>;
 
-/// Loads images on a separate thread, one at a time.
-#[derive(Default)]
+/// Loads images on a fixed number of worker threads.
+///
+/// The most recently requested images are loaded first, so what's on screen now loads first.
+/// Requests that aren't repeated get dropped, see [`Self::begin_frame`].
 pub struct ThumbnailLoader {
-    image_slots: Arc<Mutex<EntryMap<ImageSlot>>>,
+    shared: Arc<Shared>,
+}
+
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<Queue>,
+    /// Notified when there are new requests, or the number of workers should change
+    wake: Condvar,
+    /// Loaded images along with the modification time of their file, not yet written to the
+    /// thumbnail cache. The error is the type of the file if it couldn't be loaded as an image.
+    done: Mutex<EntryMap<(Result<RgbaBuf, FileType>, Option<SystemTime>)>>,
+    /// Perceptual hashes computed from the loaded images, not yet stored in the collection
+    new_phashes: Mutex<EntryMap<StoredHash>>,
+}
+
+#[derive(Default)]
+struct Queue {
+    pending: EntryMap<Request>,
+    /// Being loaded by a worker right now
+    loading: EntrySet,
+    /// Increases with every frame
+    frame: u64,
+    /// Increases with every new request, to serve requests made in the same frame in order
+    seq: u64,
+    n_workers: usize,
+    target_workers: usize,
+}
+
+struct Request {
+    path: PathBuf,
+    /// Width and height the thumbnail has to fit in
+    bounds: (u32, u32),
+    /// The last frame this was requested in
+    frame: u64,
+    seq: u64,
 }
 
 impl ThumbnailLoader {
-    pub fn request(&self, name: &Path, size: u32, uid: entry::Id) {
-        let mut slots = self.image_slots.lock();
-        if let hash_map::Entry::Vacant(e) = slots.entry(uid) {
-            e.insert(None);
-            let slots_clone = Arc::clone(&self.image_slots);
-            let name = name.to_owned();
-            ::std::thread::spawn(move || {
-                let data = match std::fs::read(name) {
-                    Ok(data) => data,
-                    Err(e) => {
-                        slots_clone
-                            .lock()
-                            .insert(uid, Some(Err(image::ImageError::IoError(e))));
-                        return;
-                    }
-                };
-                let image_result = image::load_from_memory(&data);
-                let result =
-                    image_result.map(|i| i.resize(size, size, FilterType::Triangle).to_rgba8());
-                slots_clone.lock().insert(uid, Some(result));
-            });
+    pub fn new(n_workers: usize) -> Self {
+        let loader = Self {
+            shared: Arc::default(),
+        };
+        loader.set_n_workers(n_workers);
+        loader
+    }
+    /// Start or stop workers, so there are `n` of them. Stopping ones finish their current load.
+    pub fn set_n_workers(&self, n: usize) {
+        let mut queue = self.shared.queue.lock();
+        queue.target_workers = n.max(1);
+        while queue.n_workers < queue.target_workers {
+            let shared = Arc::clone(&self.shared);
+            std::thread::spawn(move || worker(&shared));
+            queue.n_workers += 1;
+        }
+        self.shared.wake.notify_all();
+    }
+    /// Request loading the thumbnail of `name`, scaled to fit within `bounds`
+    pub fn request(&self, name: &Path, bounds: (u32, u32), uid: entry::Id) {
+        let mut queue = self.shared.queue.lock();
+        let frame = queue.frame;
+        if let Some(req) = queue.pending.get_mut(&uid) {
+            req.frame = frame;
+            return;
+        }
+        if queue.loading.contains(&uid) || self.shared.done.lock().contains_key(&uid) {
+            return;
         }
+        queue.seq += 1;
+        let seq = queue.seq;
+        queue.pending.insert(
+            uid,
+            Request {
+                path: name.to_owned(),
+                bounds,
+                frame,
+                seq,
+            },
+        );
+        self.shared.wake.notify_one();
+    }
+    /// Drop the requests that weren't repeated since the last call, they went out of view.
+    ///
+    /// Should be called once per frame, before making the requests of the frame.
+    pub fn begin_frame(&self) {
+        let mut queue = self.shared.queue.lock();
+        let last_frame = queue.frame;
+        queue.pending.retain(|_, req| req.frame == last_frame);
+        queue.frame += 1;
     }
     pub fn write_to_cache(&self, cache: &mut ThumbnailCache) {
-        let mut slots = self.image_slots.lock();
-        slots.retain(|&uid, slot| {
-            if let Some(result) = slot.take() {
-                match result {
-                    Ok(buf) => {
-                        let tex = imagebuf_to_sf_tex(buf);
-                        cache.insert(uid, Some(tex));
-                    }
-                    Err(_) => {
-                        cache.insert(uid, None);
-                    }
+        for (uid, (result, mtime)) in self.shared.done.lock().drain() {
+            cache.insert(uid, result.map(imagebuf_to_sf_tex), mtime);
+        }
+    }
+    pub fn take_phashes(&self) -> EntryMap<StoredHash> {
+        std::mem::take(&mut *self.shared.new_phashes.lock())
+    }
+    /// Whether `uid` is requested, being loaded, or loaded but not written to the cache yet
+    pub fn is_busy_with(&self, uid: entry::Id) -> bool {
+        let queue = self.shared.queue.lock();
+        queue.pending.contains_key(&uid)
+            || queue.loading.contains(&uid)
+            || self.shared.done.lock().contains_key(&uid)
+    }
+    pub fn is_idle(&self) -> bool {
+        let queue = self.shared.queue.lock();
+        queue.pending.is_empty() && queue.loading.is_empty() && self.shared.done.lock().is_empty()
+    }
+}
+
+impl Drop for ThumbnailLoader {
+    fn drop(&mut self) {
+        self.shared.queue.lock().target_workers = 0;
+        self.shared.wake.notify_all();
+    }
+}
+
+fn worker(shared: &Shared) {
+    loop {
+        let (uid, req) = {
+            let mut queue = shared.queue.lock();
+            loop {
+                if queue.n_workers > queue.target_workers {
+                    queue.n_workers -= 1;
+                    return;
                 }
-                false
-            } else {
-                true
+                if let Some(uid) = queue
+                    .pending
+                    .iter()
+                    .max_by_key(|(_, req)| (req.frame, Reverse(req.seq)))
+                    .map(|(uid, _)| *uid)
+                    && let Some(req) = queue.pending.remove(&uid)
+                {
+                    queue.loading.insert(uid);
+                    break (uid, req);
+                }
+                shared.wake.wait(&mut queue);
             }
-        });
+        };
+        // Taken before reading, so a write during loading is noticed later
+        let mtime = archive::mtime(&req.path);
+        let result = load(&req.path, req.bounds, uid, mtime, &shared.new_phashes);
+        shared.done.lock().insert(uid, (result, mtime));
+        shared.queue.lock().loading.remove(&uid);
     }
-    pub fn busy_with(&self) -> Vec<entry::Id> {
-        self.image_slots.lock().keys().copied().collect()
+}
+
+fn load(
+    path: &Path,
+    (width, height): (u32, u32),
+    uid: entry::Id,
+    mtime: Option<SystemTime>,
+    new_phashes: &Mutex<EntryMap<StoredHash>>,
+) -> Result<RgbaBuf, FileType> {
+    // Don't read in a whole video just to find out it's not an image
+    match file_type::detect(path) {
+        Ok(FileType::Image) => {}
+        Ok(ft) => return Err(ft),
+        Err(_) => return Err(FileType::Image),
     }
+    let data = archive::read(path).map_err(|_| FileType::Image)?;
+    let img = exif_meta::load_oriented(&data).map_err(|_| FileType::Image)?;
+    // We have the full image decoded anyway, so hash it while we're at it
+    let hash = phash::dhash(&img);
+    new_phashes.lock().insert(uid, StoredHash { hash, mtime });
+    Ok(img.resize(width, height, FilterType::Triangle).to_rgba8())
 }
 
 /// Convert an `image` crate image to SFML `Texture`
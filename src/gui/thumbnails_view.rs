@@ -1,5 +1,6 @@
 use {
     super::{
+        animation::HoverAnimation,
         egui_ui::EguiState,
         get_tex_for_entry,
         open::{builtin, external},
@@ -8,10 +9,12 @@ use {
         State, ThumbnailCache,
     },
     crate::{
+        archive,
         collection::{Collection, Entries},
-        dlog, entry,
+        dlog, entry, exif_meta,
+        file_type::{self, FileType},
         filter_reqs::Requirements,
-        preferences::Preferences,
+        preferences::{Preferences, ThumbnailLayout},
     },
     anyhow::Context as _,
     egui_sfml::{
@@ -19,21 +22,32 @@ use {
         sfml::{
             graphics::{
                 Color, Rect, RectangleShape, RenderStates, RenderTarget, RenderWindow, Shape,
-                Sprite, Text, Transformable,
+                Sprite, Text, Texture, Transformable,
             },
             system::Vector2f,
             window::{mouse, Event, Key},
         },
     },
+    rand::seq::SliceRandom,
+    std::{
+        ops::Range,
+        time::{Duration, Instant},
+    },
 };
 
 pub struct ThumbnailsView {
     pub thumbs_per_row: u8,
+    /// Size of the grid cells, and the height rows of the justified layout aim for
     pub thumb_size: u32,
     pub y_offset: f32,
     pub sort_by: SortBy,
-    pub uids: Vec<entry::Id>,
+    uids: Vec<entry::Id>,
+    /// Increases when `uids` changes
+    uids_gen: u64,
     pub highlight: Option<u32>,
+    layout: ThumbnailLayout,
+    width: u32,
+    justified: JustifiedRows,
 }
 
 pub enum SortBy {
@@ -42,6 +56,25 @@ pub enum SortBy {
     NTags,
 }
 
+/// Item rects of the justified rows layout, along with what they were computed from
+#[derive(Default)]
+struct JustifiedRows {
+    rects: Vec<Rect<f32>>,
+    uids_gen: u64,
+    aspect_gen: u64,
+    width: u32,
+    row_height: u32,
+    /// When the rows were last laid out because of aspect ratio changes
+    last_aspect_layout: Option<Instant>,
+}
+
+/// Newly loaded aspect ratios are applied at most this often, so the rows don't jump around
+/// with every thumbnail that loads
+const ASPECT_RELAYOUT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Aspect ratios are clamped to this range, so extreme panoramas don't make rows vanishingly thin
+const ASPECT_RANGE: (f32, f32) = (0.25, 4.0);
+
 fn thumbs_per_row_and_size(window_width: u32, preferences: &Preferences) -> (u8, u32) {
     let thumbnails_per_row = preferences.thumbs_per_row;
     let thumbnail_size = window_width / u32::from(thumbnails_per_row);
@@ -55,13 +88,19 @@ impl ThumbnailsView {
             y_offset: Default::default(),
             sort_by: SortBy::Path,
             uids: Default::default(),
+            uids_gen: 0,
             thumb_size,
             thumbs_per_row,
             highlight: None,
+            layout: preferences.thumbnail_layout,
+            width: window_width,
+            justified: JustifiedRows::default(),
         }
     }
     pub fn resize(&mut self, window_width: u32, preferences: &Preferences) {
         (self.thumbs_per_row, self.thumb_size) = thumbs_per_row_and_size(window_width, preferences);
+        self.layout = preferences.thumbnail_layout;
+        self.width = window_width;
     }
     pub fn from_collection(
         window_width: u32,
@@ -83,6 +122,14 @@ impl ThumbnailsView {
             SortBy::Path => self.uids.sort_by_key(|uid| &coll.entries[uid].path),
             SortBy::NTags => self.uids.sort_by_key(|uid| coll.entries[uid].tags.len()),
         }
+        self.uids_gen += 1;
+    }
+    pub fn shuffle(&mut self) {
+        self.uids.shuffle(&mut rand::thread_rng());
+        self.uids_gen += 1;
+    }
+    pub fn uids(&self) -> &[entry::Id] {
+        &self.uids
     }
     pub fn iter(&self) -> impl Iterator<Item = entry::Id> + '_ {
         self.uids.iter().copied()
@@ -93,29 +140,113 @@ impl ThumbnailsView {
     pub fn get(&self, index: usize) -> Option<entry::Id> {
         self.uids.get(index).copied()
     }
-    fn skip_take(&self, window_height: u32) -> (usize, usize) {
-        let thumb_size = self.thumb_size;
-        let mut thumbnails_per_column = (window_height / thumb_size) as u8;
-        // Compensate for truncating division
-        if window_height % thumb_size != 0 {
-            thumbnails_per_column += 1;
+    /// Width and height thumbnails are loaded to fit in.
+    ///
+    /// Justified rows are wider than the grid cells for wide images, so those get loaded wider.
+    pub fn thumb_bounds(&self) -> (u32, u32) {
+        match self.layout {
+            ThumbnailLayout::Grid => (self.thumb_size, self.thumb_size),
+            ThumbnailLayout::JustifiedRows => (
+                (self.thumb_size as f32 * ASPECT_RANGE.1) as u32,
+                self.thumb_size,
+            ),
+        }
+    }
+    /// Lay out the justified rows again, if anything they depend on changed.
+    ///
+    /// Thumbnails that aren't loaded yet are laid out as squares. When only aspect ratios
+    /// changed, relayouts are batched, and the topmost visible item stays in place.
+    fn update_layout(&mut self, cache: &ThumbnailCache) {
+        if self.layout != ThumbnailLayout::JustifiedRows {
+            return;
+        }
+        let just = &self.justified;
+        let reflowed = just.uids_gen != self.uids_gen
+            || just.width != self.width
+            || just.row_height != self.thumb_size;
+        let aspects_changed = just.aspect_gen != cache.aspect_gen()
+            && just
+                .last_aspect_layout
+                .is_none_or(|last| last.elapsed() >= ASPECT_RELAYOUT_INTERVAL);
+        if !reflowed && !aspects_changed {
+            return;
+        }
+        // Item at the top of the screen, and how far its top is from the top of the screen
+        let anchor = (!reflowed)
+            .then(|| {
+                let idx = just
+                    .rects
+                    .partition_point(|r| r.top + r.height <= self.y_offset);
+                Some((idx, just.rects.get(idx)?.top - self.y_offset))
+            })
+            .flatten();
+        let aspects = self.uids.iter().map(|id| {
+            cache
+                .aspect_ratio(id)
+                .map_or(1.0, |aspect| aspect.clamp(ASPECT_RANGE.0, ASPECT_RANGE.1))
+        });
+        let just = &mut self.justified;
+        just.rects = justify(aspects, self.width as f32, self.thumb_size as f32);
+        if !reflowed {
+            just.last_aspect_layout = Some(Instant::now());
+        }
+        just.uids_gen = self.uids_gen;
+        just.aspect_gen = cache.aspect_gen();
+        just.width = self.width;
+        just.row_height = self.thumb_size;
+        if let Some((idx, offset)) = anchor
+            && let Some(rect) = just.rects.get(idx)
+        {
+            self.y_offset = (rect.top - offset).max(0.0);
         }
-        // Since we can scroll, we can have another partially drawn frame per screen
-        thumbnails_per_column += 1;
-        let thumbnails_per_screen =
-            usize::from(self.thumbs_per_row) * usize::from(thumbnails_per_column);
-        let row_offset = self.y_offset as u32 / thumb_size;
-        let skip = row_offset * u32::from(self.thumbs_per_row);
-        (skip as usize, thumbnails_per_screen)
     }
-    fn find_bottom(&self, window: &RenderWindow) -> f32 {
-        let n_pics = self.iter().count();
-        let mut rows = n_pics as u32 / u32::from(self.thumbs_per_row);
-        if n_pics as u32 % u32::from(self.thumbs_per_row) != 0 {
-            rows += 1;
+    /// The range of item indices that are (at least partially) on screen
+    fn visible_range(&self, window_height: u32) -> Range<usize> {
+        match self.layout {
+            ThumbnailLayout::Grid => {
+                let thumb_size = self.thumb_size;
+                let mut thumbnails_per_column = (window_height / thumb_size) as u8;
+                // Compensate for truncating division
+                if window_height % thumb_size != 0 {
+                    thumbnails_per_column += 1;
+                }
+                // Since we can scroll, we can have another partially drawn frame per screen
+                thumbnails_per_column += 1;
+                let thumbnails_per_screen =
+                    usize::from(self.thumbs_per_row) * usize::from(thumbnails_per_column);
+                let row_offset = self.y_offset as u32 / thumb_size;
+                let skip = (row_offset * u32::from(self.thumbs_per_row)) as usize;
+                skip..skip + thumbnails_per_screen
+            }
+            ThumbnailLayout::JustifiedRows => {
+                let rects = &self.justified.rects;
+                let top = self.y_offset;
+                let bottom = top + window_height as f32;
+                let start = rects.partition_point(|r| r.top + r.height <= top);
+                let end = rects.partition_point(|r| r.top < bottom);
+                start..end.max(start)
+            }
         }
-        let bottom = rows * self.thumb_size;
-        let mut b = bottom as f32 - window.size().y as f32;
+    }
+    fn content_height(&self) -> f32 {
+        match self.layout {
+            ThumbnailLayout::Grid => {
+                let n_pics = self.iter().count();
+                let mut rows = n_pics as u32 / u32::from(self.thumbs_per_row);
+                if n_pics as u32 % u32::from(self.thumbs_per_row) != 0 {
+                    rows += 1;
+                }
+                (rows * self.thumb_size) as f32
+            }
+            ThumbnailLayout::JustifiedRows => self
+                .justified
+                .rects
+                .last()
+                .map_or(0.0, |r| r.top + r.height),
+        }
+    }
+    fn find_bottom(&self, window: &RenderWindow) -> f32 {
+        let mut b = self.content_height() - window.size().y as f32;
         if b < 0. {
             b = 0.;
         }
@@ -135,40 +266,71 @@ impl ThumbnailsView {
     fn go_to_bottom(&mut self, window: &RenderWindow) {
         self.y_offset = self.find_bottom(window);
     }
-    /// Returns the absolute thumb index at (x,y) on the screen
+    /// Returns the absolute thumb index at (x,y) on the screen, if there is a thumbnail there
     ///
     /// This is absolute, so the top left image on the screen could have a different index
     /// based on the scroll y offset
-    fn abs_thumb_index_at_xy(&self, x: i32, y: i32) -> usize {
-        let thumb_x = x as u32 / self.thumb_size;
-        let thumb_y = (y as u32 + self.y_offset as u32) / self.thumb_size;
-        let thumb_index = thumb_y * u32::from(self.thumbs_per_row) + thumb_x;
-        thumb_index as usize
+    fn abs_thumb_index_at_xy(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 {
+            return None;
+        }
+        let index = match self.layout {
+            ThumbnailLayout::Grid => {
+                let thumb_x = x as u32 / self.thumb_size;
+                if thumb_x >= u32::from(self.thumbs_per_row) {
+                    return None;
+                }
+                let thumb_y = (y as u32 + self.y_offset as u32) / self.thumb_size;
+                (thumb_y * u32::from(self.thumbs_per_row) + thumb_x) as usize
+            }
+            ThumbnailLayout::JustifiedRows => {
+                let pos = Vector2f::new(x as f32, y as f32 + self.y_offset);
+                let rects = &self.justified.rects;
+                let row_start = rects.partition_point(|r| r.top + r.height <= pos.y);
+                rects[row_start..]
+                    .iter()
+                    .take_while(|r| r.top <= pos.y)
+                    .position(|r| r.contains(pos))?
+                    + row_start
+            }
+        };
+        (index < self.uids.len()).then_some(index)
     }
     fn entry_at_xy(&self, x: i32, y: i32) -> Option<entry::Id> {
-        let thumb_index = self.abs_thumb_index_at_xy(x, y);
+        let thumb_index = self.abs_thumb_index_at_xy(x, y)?;
         self.get(thumb_index)
     }
-    /// Calculate absolute pixel position of an item at `index`
-    pub fn item_position(&self, index: u32) -> (u32, u32) {
-        let thumbs_per_row: u32 = self.thumbs_per_row.into();
-        let row = index / thumbs_per_row;
-        let pixel_y = row * self.thumb_size;
-        let col = index % thumbs_per_row;
-        let pixel_x = col * self.thumb_size;
-        (pixel_x, pixel_y)
+    /// Calculate the absolute pixel rect of the item at `index`
+    pub fn item_rect(&self, index: u32) -> Rect<f32> {
+        match self.layout {
+            ThumbnailLayout::Grid => {
+                let thumbs_per_row: u32 = self.thumbs_per_row.into();
+                let row = index / thumbs_per_row;
+                let col = index % thumbs_per_row;
+                let size = self.thumb_size as f32;
+                Rect::new(
+                    (col * self.thumb_size) as f32,
+                    (row * self.thumb_size) as f32,
+                    size,
+                    size,
+                )
+            }
+            ThumbnailLayout::JustifiedRows => self
+                .justified
+                .rects
+                .get(index as usize)
+                .copied()
+                .unwrap_or_default(),
+        }
     }
     pub fn seek_to_contain_index(&mut self, index: usize, height: u32) {
-        let (_x, y) = self.item_position(index as u32);
+        let rect = self.item_rect(index as u32);
         let view_y = &mut self.y_offset;
-        let thumb_size = self.thumb_size;
-        if y < (*view_y as u32) {
-            let diff = (*view_y as u32) - y;
-            *view_y -= diff as f32;
+        if rect.top < *view_y {
+            *view_y = rect.top;
         }
-        if y + thumb_size > (*view_y as u32 + height) {
-            let diff = (y + thumb_size) - (*view_y as u32 + height);
-            *view_y += diff as f32;
+        if rect.top + rect.height > *view_y + height as f32 {
+            *view_y = rect.top + rect.height - height as f32;
         }
     }
     pub fn highlight_and_seek_to_entry(&mut self, id: entry::Id, height: u32) -> bool {
@@ -183,6 +345,38 @@ impl ThumbnailsView {
     }
 }
 
+/// Lay out items with the given aspect ratios into rows filling `width`.
+///
+/// Rows are shrunk from `row_height` until they fit. The last row isn't stretched.
+fn justify(aspects: impl Iterator<Item = f32>, width: f32, row_height: f32) -> Vec<Rect<f32>> {
+    let mut rects = Vec::new();
+    let mut row = Vec::new();
+    let mut row_aspect = 0.0;
+    let mut y = 0.0;
+    for aspect in aspects {
+        row.push(aspect);
+        row_aspect += aspect;
+        if row_aspect * row_height >= width {
+            let height = width / row_aspect;
+            push_row(&mut rects, &row, y, height);
+            y += height;
+            row.clear();
+            row_aspect = 0.0;
+        }
+    }
+    push_row(&mut rects, &row, y, row_height);
+    rects
+}
+
+fn push_row(rects: &mut Vec<Rect<f32>>, row: &[f32], y: f32, height: f32) {
+    let mut x = 0.0;
+    for aspect in row {
+        let width = aspect * height;
+        rects.push(Rect::new(x, y, width, height));
+        x += width;
+    }
+}
+
 pub(super) fn draw_thumbnails(
     state: &mut State,
     res: &Resources,
@@ -190,27 +384,58 @@ pub(super) fn draw_thumbnails(
     entries: &Entries,
     load_anim_rotation: f32,
     pointer_active: bool,
+    animate_on_hover: bool,
 ) {
     let mouse_pos = window.mouse_position();
-    let thumb_size = state.thumbs_view.thumb_size;
+    let thumb_bounds = state.thumbs_view.thumb_bounds();
+    state.thumbnail_cache.begin_frame();
     state
         .thumbnail_loader
         .write_to_cache(&mut state.thumbnail_cache);
+    state.thumbnail_loader.begin_frame();
+    state.thumbs_view.update_layout(&state.thumbnail_cache);
+    let visible = state.thumbs_view.visible_range(window.size().y);
+    let visible_ids = state
+        .thumbs_view
+        .uids
+        .iter()
+        .copied()
+        .skip(visible.start)
+        .take(visible.len());
+    for id in state.thumbnail_cache.remove_stale(visible_ids, entries) {
+        state.invalidate_entry(id);
+    }
+    let hovered = pointer_active
+        .then(|| state.thumbs_view.entry_at_xy(mouse_pos.x, mouse_pos.y))
+        .flatten();
+    match hovered {
+        Some(id) if animate_on_hover => {
+            if state.hover_anim.as_ref().is_none_or(|anim| anim.id != id) {
+                state.hover_anim = entries
+                    .get(&id)
+                    .and_then(|en| HoverAnimation::start(id, &en.path, thumb_bounds));
+            }
+        }
+        _ => state.hover_anim = None,
+    }
+    if let Some(anim) = &mut state.hover_anim {
+        anim.update();
+    }
+    let hover_frame = state
+        .hover_anim
+        .as_ref()
+        .and_then(|anim| Some((anim.id, anim.frame()?)));
     let mut sprite = Sprite::new();
-    let (skip, take) = state.thumbs_view.skip_take(window.size().y);
-    for (rel_idx, (abs_idx, uid)) in state
+    for (abs_idx, uid) in state
         .thumbs_view
         .iter()
         .enumerate()
-        .skip(skip)
-        .take(take)
-        .enumerate()
+        .skip(visible.start)
+        .take(visible.len())
     {
-        let column = (rel_idx as u32) % u32::from(state.thumbs_view.thumbs_per_row);
-        let row = (rel_idx as u32) / u32::from(state.thumbs_view.thumbs_per_row);
-        let x = (column * thumb_size) as f32;
-        let y = (row * thumb_size) as f32 - (state.thumbs_view.y_offset % thumb_size as f32);
-        let image_rect = Rect::new(x, y, thumb_size as f32, thumb_size as f32);
+        let mut image_rect = state.thumbs_view.item_rect(abs_idx as u32);
+        image_rect.top -= state.thumbs_view.y_offset;
+        let (x, y) = (image_rect.left, image_rect.top);
         let mouse_over = image_rect.contains(Vector2f::new(mouse_pos.x as f32, mouse_pos.y as f32));
         if state.sel.current_contains(&uid) {
             sprite.set_color(Color::GREEN);
@@ -221,14 +446,14 @@ pub(super) fn draw_thumbnails(
             &state.thumbnail_cache,
             entries,
             window,
-            x,
-            y,
+            image_rect,
             uid,
-            thumb_size,
+            thumb_bounds,
             &mut sprite,
             res,
             &state.thumbnail_loader,
             load_anim_rotation,
+            hover_frame.and_then(|(id, tex)| (id == uid).then_some(tex)),
         );
         if mouse_over && pointer_active {
             let mut rs = RectangleShape::from_rect(image_rect);
@@ -245,6 +470,30 @@ pub(super) fn draw_thumbnails(
             window.draw(&s);
         }
     }
+    prefetch(state, entries, visible);
+}
+
+/// Rows of thumbnails above and below the visible ones to load ahead of time
+const PREFETCH_ROWS: usize = 2;
+
+/// Request the thumbnails around the visible ones, so they are ready when scrolled into view
+fn prefetch(state: &State, entries: &Entries, visible: Range<usize>) {
+    let margin = PREFETCH_ROWS * usize::from(state.thumbs_view.thumbs_per_row);
+    let below = visible.end..visible.end + margin;
+    let above = (visible.start.saturating_sub(margin)..visible.start).rev();
+    for id in below
+        .chain(above)
+        .filter_map(|idx| state.thumbs_view.get(idx))
+    {
+        // Looking it up also keeps it from being evicted
+        if state.thumbnail_cache.get(&id).is_none()
+            && let Some(entry) = entries.get(&id)
+        {
+            state
+                .thumbnail_loader
+                .request(&entry.path, state.thumbs_view.thumb_bounds(), id);
+        }
+    }
 }
 
 #[expect(clippy::too_many_arguments)]
@@ -252,26 +501,38 @@ fn draw_thumbnail<'a: 'b, 'b>(
     thumbnail_cache: &'a ThumbnailCache,
     entries: &Entries,
     window: &mut RenderWindow,
-    x: f32,
-    y: f32,
+    rect: Rect<f32>,
     id: entry::Id,
-    thumb_size: u32,
+    thumb_bounds: (u32, u32),
     sprite: &mut Sprite<'b>,
     res: &'a Resources,
     thumbnail_loader: &ThumbnailLoader,
     load_anim_rotation: f32,
+    hover_frame: Option<&'b Texture>,
 ) {
-    let (has_img, texture) = get_tex_for_entry(
-        thumbnail_cache,
-        id,
-        entries,
-        thumbnail_loader,
-        thumb_size,
-        res,
-    );
+    let (has_img, texture) = match hover_frame {
+        Some(tex) => (true, tex),
+        None => get_tex_for_entry(
+            thumbnail_cache,
+            id,
+            entries,
+            thumbnail_loader,
+            thumb_bounds,
+            res,
+        ),
+    };
+    let (x, y) = (rect.left, rect.top);
     sprite.set_texture(texture, true);
     sprite.set_position((x, y));
-    if thumbnail_loader.busy_with().contains(&id) {
+    // Fill the rect, which can be larger than the texture if it was loaded for another layout
+    let tex_size = texture.size();
+    let scale = if has_img {
+        (rect.width / tex_size.x as f32).min(rect.height / tex_size.y as f32)
+    } else {
+        1.0
+    };
+    sprite.set_scale((scale, scale));
+    if thumbnail_loader.is_busy_with(id) {
         sprite.set_origin((27.0, 6.0));
         sprite.move_((48.0, 48.0));
         sprite.set_rotation(load_anim_rotation);
@@ -334,7 +595,9 @@ pub(in crate::gui) fn handle_event(
                         sel_buf.buf.push(uid);
                     }
                 } else if Key::LControl.is_pressed() {
-                    let curr_thumb_idx = state.thumbs_view.abs_thumb_index_at_xy(x, y);
+                    let Some(curr_thumb_idx) = state.thumbs_view.abs_thumb_index_at_xy(x, y) else {
+                        return;
+                    };
                     match state.select_a {
                         Some(a) => {
                             let Some(sel_buf) = state.sel.current_mut() else {
@@ -350,16 +613,17 @@ pub(in crate::gui) fn handle_event(
                         None => state.select_a = Some(curr_thumb_idx),
                     }
                 } else {
+                    // The built-in viewer can only show images, other files go to their app.
+                    // Images inside archives can only be shown by the built-in viewer.
+                    let path = &coll.entries[&uid].path;
+                    let is_image = matches!(file_type::detect(path), Ok(FileType::Image));
+                    let in_archive = archive::split_member(path).is_some();
                     let result: anyhow::Result<()> = try {
-                        if preferences.use_built_in_viewer {
-                            if let Some(id) = state.thumbs_view.entry_at_xy(x, y) {
-                                builtin::open_single_with_others(
-                                    id,
-                                    coll,
-                                    state,
-                                    window,
-                                    state.thumbs_view.abs_thumb_index_at_xy(x, y),
-                                )?;
+                        if (preferences.use_built_in_viewer || in_archive) && is_image {
+                            if let Some(idx) = state.thumbs_view.abs_thumb_index_at_xy(x, y)
+                                && let Some(id) = state.thumbs_view.get(idx)
+                            {
+                                builtin::open_single_with_others(id, coll, state, window, idx)?;
                             }
                         } else {
                             external::open_single_with_others(coll, uid, preferences)?;
@@ -470,8 +734,8 @@ fn copy_image_to_clipboard(
 ) -> anyhow::Result<()> {
     use arboard::ImageData;
     let imgpath = &coll.entries[&uid].path;
-    let buf = std::fs::read(imgpath)?;
-    let img = image::load_from_memory(&buf).context("Failed to load image from memory")?;
+    let buf = archive::read(imgpath)?;
+    let img = exif_meta::load_oriented(&buf).context("Failed to load image from memory")?;
     let rgba = img.to_rgba8();
     let img_data = ImageData {
         width: rgba.width() as usize,
@@ -532,3 +796,41 @@ pub(in crate::gui) fn search_goto_cursor(state: &mut State, coll: &Collection, v
         state.search_success = false;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {super::justify, egui_sfml::sfml::graphics::Rect};
+
+    #[test]
+    fn justify_empty() {
+        assert!(justify([].into_iter(), 200.0, 100.0).is_empty());
+    }
+
+    #[test]
+    fn justify_fills_rows_and_leaves_last_row_alone() {
+        let rects = justify([1.0, 1.0, 1.0].into_iter(), 200.0, 100.0);
+        assert_eq!(
+            rects,
+            [
+                Rect::new(0.0, 0.0, 100.0, 100.0),
+                Rect::new(100.0, 0.0, 100.0, 100.0),
+                Rect::new(0.0, 100.0, 100.0, 100.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn justify_shrinks_rows_to_fit() {
+        let rects = justify([1.5, 1.5, 4.0, 0.5].into_iter(), 200.0, 100.0);
+        assert_eq!(rects.len(), 4);
+        // Two 1.5 aspect items don't fit at full height
+        let first_row = &rects[..2];
+        let height = 200.0 / 3.0;
+        assert!(first_row.iter().all(|r| r.top == 0.0 && r.height == height));
+        let row_width: f32 = first_row.iter().map(|r| r.width).sum();
+        assert!((row_width - 200.0).abs() < 1e-3);
+        // An item wider than the row gets a row of its own
+        assert_eq!(rects[2], Rect::new(0.0, height, 200.0, 50.0));
+        assert_eq!(rects[3], Rect::new(0.0, height + 50.0, 50.0, 100.0));
+    }
+}
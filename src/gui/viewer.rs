@@ -1,15 +1,27 @@
 use {
-    super::{resources::Resources, thumbnail_loader::imagebuf_to_sf_tex, Activity, State},
-    crate::{collection::Collection, dlog, entry},
+    super::{
+        animation::{self, Animation},
+        edit_watch,
+        resources::Resources,
+        Activity, State,
+    },
+    crate::{archive, collection::Collection, db::EntryMap, dlog, entry, exif_meta},
     egui_sfml::{
         egui,
         sfml::{
-            cpp::FBox,
-            graphics::{RenderTarget, RenderWindow, Sprite, Text, Texture, Transformable},
+            graphics::{
+                Color, Rect, RectangleShape, RenderTarget, RenderWindow, Shape, Sprite, Text,
+                Transformable,
+            },
             window::{mouse, Event, Key},
         },
     },
-    std::{collections::VecDeque, time::Instant},
+    std::{
+        collections::VecDeque,
+        fmt::Write as _,
+        path::Path,
+        time::{Instant, SystemTime},
+    },
 };
 
 pub(super) fn draw(
@@ -24,16 +36,25 @@ pub(super) fn draw(
     }
     let id = state.viewer_state.image_list[state.viewer_state.index];
     let entry = &coll.entries[&id];
-    match state.viewer_state.image_cache.get(id) {
+    if state.viewer_state.is_stale(id, &entry.path) {
+        state.invalidate_entry(id);
+    }
+    let viewer_state = &mut state.viewer_state;
+    match viewer_state.image_cache.get_mut(id) {
         Some(result) => match result {
-            Ok(tex) => {
-                let mut spr = Sprite::with_texture(tex);
+            Ok(img) => {
+                img.anim
+                    .update(viewer_state.anim_paused, viewer_state.anim_speed);
+                let mut spr = Sprite::with_texture(img.anim.tex());
                 spr.move_((
-                    state.viewer_state.image_offset.0 as f32,
-                    state.viewer_state.image_offset.1 as f32,
+                    viewer_state.image_offset.0 as f32,
+                    viewer_state.image_offset.1 as f32,
                 ));
-                spr.set_scale((state.viewer_state.scale, state.viewer_state.scale));
+                spr.set_scale((viewer_state.scale, viewer_state.scale));
                 window.draw(&spr);
+                if viewer_state.show_info {
+                    draw_info_overlay(window, res, &entry.path, img);
+                }
             }
             Err(e) => {
                 let mut text = Text::new(&e.to_string(), &res.font, 20);
@@ -42,30 +63,55 @@ pub(super) fn draw(
             }
         },
         None => {
-            let data = match std::fs::read(&entry.path) {
-                Ok(data) => data,
+            // Taken before reading, so a write during loading is noticed later.
+            // Errors get it too, so fixing a broken file loads it again.
+            let mtime = archive::mtime(&entry.path);
+            let result = match archive::read(&entry.path) {
+                Ok(data) => match animation::decode(&data) {
+                    Ok(frames) => Ok(ViewerImage {
+                        anim: Animation::new(frames),
+                        exif: exif_meta::fields(&data),
+                    }),
+                    Err(e) => Err(anyhow::anyhow!(e)),
+                },
                 Err(e) => {
                     dlog!("Error loading image: {e}");
-                    return;
+                    Err(e.into())
                 }
             };
-            match image::load_from_memory(&data) {
-                Ok(img) => {
-                    let tex = imagebuf_to_sf_tex(img.to_rgba8());
-                    state.viewer_state.image_cache.insert((id, Ok(tex)));
-                }
-                Err(e) => {
-                    state
-                        .viewer_state
-                        .image_cache
-                        .insert((id, Err(anyhow::anyhow!(e))));
-                }
-            }
-            state.viewer_state.reset_view(window);
+            viewer_state.image_cache.insert((id, result, mtime));
+            viewer_state.reset_view(window);
         }
     }
 }
 
+fn draw_info_overlay(window: &mut RenderWindow, res: &Resources, path: &Path, img: &ViewerImage) {
+    let size = img.anim.tex().size();
+    let mut info = format!("{}\n{}x{}", path.display(), size.x, size.y);
+    if img.anim.is_animated() {
+        write!(info, ", {} frames", img.anim.n_frames()).unwrap();
+    }
+    info.push('\n');
+    if img.exif.is_empty() {
+        info.push_str("\nNo EXIF data");
+    }
+    for (tag, value) in &img.exif {
+        write!(info, "\n{tag}: {value}").unwrap();
+    }
+    let mut text = Text::new(&info, &res.font, 14);
+    text.set_position((16., 48.));
+    let bounds = text.global_bounds();
+    let mut bg = RectangleShape::from_rect(Rect::new(
+        bounds.left - 8.,
+        bounds.top - 8.,
+        bounds.width + 16.,
+        bounds.height + 16.,
+    ));
+    bg.set_fill_color(Color::rgba(0, 0, 0, 192));
+    window.draw(&bg);
+    window.draw(&text);
+}
+
 pub(super) fn handle_event(state: &mut State, event: &Event, window: &RenderWindow) {
     match *event {
         Event::KeyPressed { code, shift, .. } => match code {
@@ -77,6 +123,10 @@ pub(super) fn handle_event(state: &mut State, event: &Event, window: &RenderWind
             Key::Hyphen => state.viewer_state.zoom_out(),
             Key::Delete => state.viewer_state.remove_from_view_list(),
             Key::R => state.viewer_state.reset_view(window),
+            Key::I => state.viewer_state.show_info ^= true,
+            Key::Space => state.viewer_state.anim_paused ^= true,
+            Key::Comma => state.viewer_state.step_frame(false),
+            Key::Period => state.viewer_state.step_frame(true),
             _ => {}
         },
         Event::MouseButtonPressed {
@@ -103,38 +153,80 @@ pub(super) fn handle_event(state: &mut State, event: &Event, window: &RenderWind
     }
 }
 
-type ImageResult = Result<FBox<Texture>, anyhow::Error>;
-type CacheKvPair = (entry::Id, ImageResult);
+struct ViewerImage {
+    anim: Animation,
+    /// EXIF fields, as (tag, value) display strings
+    exif: Vec<(String, String)>,
+}
+
+type ImageResult = Result<ViewerImage, anyhow::Error>;
+/// Entry, its image, and the modification time of its file when it was loaded
+type CacheKvPair = (entry::Id, ImageResult, Option<SystemTime>);
 
+/// Recently viewed images. The oldest ones are dropped when there are more than `capacity`, or
+/// their textures take up more than [`CACHE_BUDGET`]. Animations have a texture per frame, so a
+/// few large ones can take up a lot.
 struct ImageCache {
     img_results: VecDeque<CacheKvPair>,
     capacity: usize,
+    /// Bytes taken up by the textures
+    used: usize,
 }
 
+const CACHE_BUDGET: usize = 512 * 1024 * 1024;
+
 impl Default for ImageCache {
     fn default() -> Self {
         Self {
             img_results: Default::default(),
             capacity: 100,
+            used: 0,
         }
     }
 }
 
+fn result_bytes(result: &ImageResult) -> usize {
+    result.as_ref().map_or(0, |img| img.anim.n_bytes())
+}
+
 impl ImageCache {
     fn get(&self, id: entry::Id) -> Option<&ImageResult> {
         self.img_results
             .iter()
             .find_map(|kvpair| (kvpair.0 == id).then_some(&kvpair.1))
     }
+    fn get_mut(&mut self, id: entry::Id) -> Option<&mut ImageResult> {
+        self.img_results
+            .iter_mut()
+            .find_map(|kvpair| (kvpair.0 == id).then_some(&mut kvpair.1))
+    }
+    fn mtime(&self, id: entry::Id) -> Option<Option<SystemTime>> {
+        self.img_results
+            .iter()
+            .find_map(|kvpair| (kvpair.0 == id).then_some(kvpair.2))
+    }
+    fn remove(&mut self, id: entry::Id) {
+        let used = &mut self.used;
+        self.img_results.retain(|kvpair| {
+            if kvpair.0 == id {
+                *used -= result_bytes(&kvpair.1);
+            }
+            kvpair.0 != id
+        });
+    }
     fn insert(&mut self, kvpair: CacheKvPair) {
+        self.used += result_bytes(&kvpair.1);
         self.img_results.push_back(kvpair);
-        if self.img_results.len() > self.capacity {
-            self.img_results.pop_front();
+        // The image just inserted is the one being shown, so it always stays
+        while self.img_results.len() > 1
+            && (self.img_results.len() > self.capacity || self.used > CACHE_BUDGET)
+            && let Some(oldest) = self.img_results.pop_front()
+        {
+            self.used -= result_bytes(&oldest.1);
         }
     }
 }
 
-#[derive(Default)]
 pub struct ViewerState {
     pub index: usize,
     image_cache: ImageCache,
@@ -144,21 +236,100 @@ pub struct ViewerState {
     pub image_list: Vec<entry::Id>,
     pub slideshow_timer_ms: u32,
     pub last_slideshow_instant: Option<Instant>,
+    anim_paused: bool,
+    /// Multiplier for the playback speed of animations
+    anim_speed: f32,
+    /// Show information about the image, including its EXIF data
+    show_info: bool,
+    last_mtime_check: Instant,
+}
+
+impl Default for ViewerState {
+    fn default() -> Self {
+        Self {
+            index: 0,
+            image_cache: ImageCache::default(),
+            scale: 0.0,
+            image_offset: (0, 0),
+            grab_origin: None,
+            image_list: Vec::new(),
+            slideshow_timer_ms: 0,
+            last_slideshow_instant: None,
+            anim_paused: false,
+            anim_speed: 1.0,
+            show_info: false,
+            last_mtime_check: Instant::now(),
+        }
+    }
 }
 
 impl ViewerState {
+    /// Replace the old entry ids with the new ones after compacting Uids
+    pub(in crate::gui) fn remap_entry_ids(&mut self, map: &EntryMap<entry::Id>) {
+        self.image_list = self
+            .image_list
+            .iter()
+            .filter_map(|id| map.get(id).copied())
+            .collect();
+        self.index = self.index.min(self.image_list.len().saturating_sub(1));
+        self.image_cache = ImageCache::default();
+    }
+    /// Keep only the ids in the image list that `keep` agrees with, staying on the current one
+    /// if it's kept
+    pub(in crate::gui) fn retain_entries(&mut self, mut keep: impl FnMut(entry::Id) -> bool) {
+        let current = self.image_list.get(self.index).copied();
+        self.image_list.retain(|&id| keep(id));
+        self.index = current
+            .and_then(|cur| self.image_list.iter().position(|&id| id == cur))
+            .unwrap_or(self.index)
+            .min(self.image_list.len().saturating_sub(1));
+    }
+    /// Forget the loaded image of `id`, so it gets loaded again
+    pub(in crate::gui) fn invalidate(&mut self, id: entry::Id) {
+        self.image_cache.remove(id);
+    }
+    /// Whether the file of the loaded image (or error) of `id` changed since loading.
+    ///
+    /// Only checks every [`edit_watch::CHECK_INTERVAL`].
+    fn is_stale(&mut self, id: entry::Id, path: &Path) -> bool {
+        if self.last_mtime_check.elapsed() < edit_watch::CHECK_INTERVAL {
+            return false;
+        }
+        self.last_mtime_check = Instant::now();
+        self.image_cache
+            .mtime(id)
+            .is_some_and(|mtime| archive::mtime(path) != mtime)
+    }
     pub(in crate::gui) fn reset_view(&mut self, window: &RenderWindow) {
         self.scale = 1.0;
         self.image_offset = (0, 0);
         let id = self.image_list[self.index];
         if let Some(Ok(img)) = self.image_cache.get(id) {
-            let img_size = img.size();
+            let img_size = img.anim.tex().size();
             let win_size = window.size();
             if img_size.y > win_size.y {
                 self.scale = win_size.y as f32 / img_size.y as f32;
             }
         }
     }
+    /// Pause the animation of the current image, and go a frame forward or backward
+    pub(in crate::gui) fn step_frame(&mut self, forward: bool) {
+        let id = self.image_list[self.index];
+        if let Some(Ok(img)) = self.image_cache.get_mut(id) {
+            self.anim_paused = true;
+            img.anim.step(forward);
+        }
+    }
+    /// The current frame and the number of frames, if the current image is animated
+    fn frame_info(&self) -> Option<(usize, usize)> {
+        let id = *self.image_list.get(self.index)?;
+        match self.image_cache.get(id) {
+            Some(Ok(img)) if img.anim.is_animated() => {
+                Some((img.anim.current_frame(), img.anim.n_frames()))
+            }
+            _ => None,
+        }
+    }
     pub(in crate::gui) fn remove_from_view_list(&mut self) {
         self.image_list.remove(self.index);
         self.index = self.index.saturating_sub(1);
@@ -218,10 +389,36 @@ pub fn menu_ui(ui: &mut egui::Ui, state: &mut State, win: &RenderWindow) {
         if ui.button("Reset View (R)").clicked() {
             state.viewer_state.reset_view(win);
         }
+        ui.checkbox(&mut state.viewer_state.show_info, "Information (I)");
         ui.separator();
         if ui.button("Remove from view list (Del)").clicked() {
             state.viewer_state.remove_from_view_list();
         }
+        ui.separator();
+        ui.label("Animation");
+        let pause_label = if state.viewer_state.anim_paused {
+            "Play (Space)"
+        } else {
+            "Pause (Space)"
+        };
+        if ui.button(pause_label).clicked() {
+            state.viewer_state.anim_paused ^= true;
+        }
+        if ui.button("Previous frame (,)").clicked() {
+            state.viewer_state.step_frame(false);
+        }
+        if ui.button("Next frame (.)").clicked() {
+            state.viewer_state.step_frame(true);
+        }
+        ui.add(
+            egui::Slider::new(&mut state.viewer_state.anim_speed, 0.1..=4.0)
+                .text("Speed")
+                .logarithmic(true),
+        );
+        if let Some((current, n_frames)) = state.viewer_state.frame_info() {
+            ui.label(format!("Frame {}/{n_frames}", current + 1));
+        }
+        ui.separator();
         ui.label("Slideshow timer");
         ui.add(egui::DragValue::new(
             &mut state.viewer_state.slideshow_timer_ms,
@@ -0,0 +1,176 @@
+//! Importing external files into the collection folder
+//!
+//! Destination paths are relative to the collection root, which is the working directory.
+
+use {
+    crate::{
+        collection::Collection,
+        db::UidCounter,
+        folder_scan,
+        move_rules::{is_inside_root, numbered_free_path},
+        tag,
+    },
+    anyhow::{bail, Context as _},
+    fnv::FnvHashSet,
+    std::{
+        fs,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Whether imported files are copied or moved into the collection
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImportMode {
+    #[default]
+    Copy,
+    Move,
+}
+
+impl ImportMode {
+    pub const ALL: [Self; 2] = [Self::Copy, Self::Move];
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Copy => "Copy",
+            Self::Move => "Move",
+        }
+    }
+}
+
+/// A file to import, and where it ends up
+pub struct ImportFile {
+    /// Absolute path of the external file
+    pub src: PathBuf,
+    /// Relative to the collection root
+    pub dest: PathBuf,
+}
+
+#[derive(Default)]
+pub struct ImportPlan {
+    pub files: Vec<ImportFile>,
+    /// Number of files that get a number added to their name, because the name is taken
+    pub renamed: usize,
+}
+
+/// Resolve tag names for the imported files. Unknown names are an error.
+pub fn resolve_tags(coll: &Collection, text: &str) -> anyhow::Result<Vec<tag::Id>> {
+    let mut ids = Vec::new();
+    for word in text.split_whitespace() {
+        match coll.resolve_tag(word) {
+            Some(id) => ids.push(id),
+            None => {
+                let similar = coll.similar_tag_names(word, 3);
+                if similar.is_empty() {
+                    bail!("Unknown tag: {word}");
+                }
+                bail!("Unknown tag: {word}. Did you mean: {}?", similar.join(", "));
+            }
+        }
+    }
+    Ok(ids)
+}
+
+/// Work out where each file of `sources` goes inside `dest_folder`.
+///
+/// Folders are imported with their contents, keeping their structure.
+/// Taken destination names get a number added to them.
+pub fn plan(
+    coll: &Collection,
+    sources: &[PathBuf],
+    dest_folder: &Path,
+) -> anyhow::Result<ImportPlan> {
+    if !is_inside_root(dest_folder) {
+        bail!("Destination folder must be inside the collection");
+    }
+    let mut taken: FnvHashSet<PathBuf> = coll.entries.values().map(|en| en.path.clone()).collect();
+    let mut plan = ImportPlan::default();
+    for src in sources {
+        let Some(name) = src.file_name() else {
+            bail!("Can't import {}", src.display());
+        };
+        let meta = fs::metadata(src).with_context(|| format!("Can't import {}", src.display()))?;
+        let mut found = Vec::new();
+        if meta.is_dir() {
+            let walk = ignore::WalkBuilder::new(src)
+                .standard_filters(false)
+                .sort_by_file_name(|a, b| a.cmp(b))
+                .build();
+            for dir_entry in walk {
+                let dir_entry = dir_entry?;
+                if !dir_entry.file_type().is_some_and(|ft| ft.is_file()) {
+                    continue;
+                }
+                let rel = dir_entry.path().strip_prefix(src)?;
+                found.push((
+                    dir_entry.path().to_owned(),
+                    dest_folder.join(name).join(rel),
+                ));
+            }
+        } else {
+            found.push((src.clone(), dest_folder.join(name)));
+        }
+        for (src, mut dest) in found {
+            if folder_scan::has_ignored_ext(&src, &coll.ignored_extensions) {
+                continue;
+            }
+            let is_taken = |path: &Path| taken.contains(path) || path.exists();
+            if is_taken(&dest) {
+                dest = numbered_free_path(&dest, is_taken);
+                plan.renamed += 1;
+            }
+            taken.insert(dest.clone());
+            plan.files.push(ImportFile { src, dest });
+        }
+    }
+    Ok(plan)
+}
+
+/// Copy or move the planned files into the collection, and add entries for them with `tags`.
+///
+/// Returns the errors of the files that couldn't be imported.
+pub fn execute(
+    coll: &mut Collection,
+    uid_counter: &mut UidCounter,
+    plan: ImportPlan,
+    mode: ImportMode,
+    tags: &[tag::Id],
+) -> Vec<String> {
+    let mut errors = Vec::new();
+    for file in plan.files {
+        if let Err(e) = transfer(&file.src, &file.dest, mode) {
+            errors.push(format!("{}: {e}", file.src.display()));
+            continue;
+        }
+        let id = coll.add_new_entry(file.dest, uid_counter);
+        for tag in tags {
+            // The entry was just added, this can't fail
+            let _ = coll.add_tag_for(id, *tag);
+        }
+    }
+    errors
+}
+
+/// Copy or move `src` to `dest`, creating the folders leading to it
+pub(crate) fn transfer(src: &Path, dest: &Path, mode: ImportMode) -> anyhow::Result<()> {
+    // Could have appeared since planning
+    if dest.exists() {
+        bail!("Destination {} already exists", dest.display());
+    }
+    if let Some(parent) = dest.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        fs::create_dir_all(parent)?;
+    }
+    match mode {
+        ImportMode::Copy => {
+            fs::copy(src, dest)?;
+        }
+        ImportMode::Move => {
+            // Renaming fails across file systems, fall back to copying
+            if fs::rename(src, dest).is_err() {
+                fs::copy(src, dest)?;
+                fs::remove_file(src)?;
+            }
+        }
+    }
+    Ok(())
+}
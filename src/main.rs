@@ -1,4 +1,4 @@
-#![feature(try_blocks, let_chains, map_many_mut)]
+#![feature(try_blocks, let_chains, map_many_mut, substr_range)]
 #![windows_subsystem = "windows"]
 #![warn(
     unused_qualifications,
@@ -23,13 +23,25 @@
 )]
 
 mod application;
+mod archive;
+mod coll_extract;
+mod coll_merge;
 mod collection;
+mod compact;
 mod db;
+mod dup_finder;
 mod entry;
 mod entry_utils;
+mod exif_meta;
+mod file_type;
 mod filter_reqs;
 mod folder_scan;
+mod folder_watch;
+mod fsck;
 mod gui;
+mod import;
+mod move_rules;
+mod phash;
 mod preferences;
 mod sequence;
 mod serialization;
@@ -0,0 +1,231 @@
+//! Rules for moving entries into folders based on their tags
+//!
+//! Paths are relative to the collection root, which is the working directory.
+
+use {
+    crate::{archive, collection::Collection, entry, filter_reqs::Requirements},
+    anyhow::{anyhow, bail},
+    fnv::FnvHashSet,
+    serde_derive::{Deserialize, Serialize},
+    std::path::{Component, Path, PathBuf},
+};
+
+/// How many move batches the journal keeps for undoing
+const MAX_JOURNAL_LEN: usize = 32;
+
+/// Entries matching `query` get moved into `folder`
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct MoveRule {
+    pub query: String,
+    /// Relative to the collection root
+    pub folder: PathBuf,
+    pub enabled: bool,
+}
+
+/// What to do when the destination of a move is already taken
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum CollisionPolicy {
+    #[default]
+    Skip,
+    /// Add a number to the file name, like `name (2).png`
+    AddNumber,
+}
+
+impl CollisionPolicy {
+    pub const ALL: [Self; 2] = [Self::Skip, Self::AddNumber];
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Skip => "Skip",
+            Self::AddNumber => "Add number to name",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Move {
+    pub id: entry::Id,
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// The moves a run of the rules would do
+#[derive(Default)]
+pub struct MovePlan {
+    pub moves: Vec<Move>,
+    /// Moves that weren't planned, because the destination is taken
+    pub collisions: Vec<Move>,
+}
+
+/// Moves done in one go, in the order they were done
+#[derive(Serialize, Deserialize)]
+pub struct MoveBatch {
+    pub moves: Vec<Move>,
+    /// Folders the moves had to create, so undoing can clean them up
+    #[serde(default)]
+    pub created_dirs: Vec<PathBuf>,
+}
+
+/// Work out where the enabled rules would move entries. The first matching rule wins.
+pub fn plan(coll: &Collection, collisions: CollisionPolicy) -> anyhow::Result<MovePlan> {
+    let mut rules = Vec::new();
+    for (i, rule) in coll.move_rules.iter().enumerate() {
+        if !rule.enabled {
+            continue;
+        }
+        if !is_inside_root(&rule.folder) {
+            bail!("Rule {}: folder must be inside the collection", i + 1);
+        }
+        let mut reqs = Requirements::default();
+        reqs.parse_and_resolve(&rule.query, coll)
+            .map_err(|e| anyhow!("Rule {}: {e}", i + 1))?;
+        rules.push((reqs, &rule.folder));
+    }
+    let mut taken: FnvHashSet<PathBuf> = coll.entries.values().map(|en| en.path.clone()).collect();
+    let mut ids: Vec<entry::Id> = coll.entries.keys().copied().collect();
+    ids.sort_by_key(|id| &coll.entries[id].path);
+    let mut plan = MovePlan::default();
+    for id in ids {
+        let en = &coll.entries[&id];
+        // Images inside archives can't be moved on their own
+        if archive::split_member(&en.path).is_some() {
+            continue;
+        }
+        let Some((_, folder)) = rules
+            .iter()
+            .find(|(reqs, _)| en.all_reqs_satisfied(id, reqs, &coll.tags, &coll.sequences))
+        else {
+            continue;
+        };
+        let Some(file_name) = en.path.file_name() else {
+            continue;
+        };
+        let mut to = folder.join(file_name);
+        if to == en.path {
+            continue;
+        }
+        let is_taken = |path: &Path| taken.contains(path) || path.exists();
+        if is_taken(&to) {
+            match collisions {
+                CollisionPolicy::Skip => {
+                    plan.collisions.push(Move {
+                        id,
+                        from: en.path.clone(),
+                        to,
+                    });
+                    continue;
+                }
+                CollisionPolicy::AddNumber => to = numbered_free_path(&to, is_taken),
+            }
+        }
+        taken.insert(to.clone());
+        plan.moves.push(Move {
+            id,
+            from: en.path.clone(),
+            to,
+        });
+    }
+    Ok(plan)
+}
+
+/// Do the planned moves, updating the entry paths.
+///
+/// Returns the errors of the moves that failed. The successful ones are added to the journal.
+pub fn execute(coll: &mut Collection, plan: &MovePlan) -> Vec<String> {
+    let mut done = Vec::new();
+    let mut created_dirs = Vec::new();
+    let mut errors = Vec::new();
+    for mv in &plan.moves {
+        match move_entry(coll, mv.id, &mv.from, &mv.to, &mut created_dirs) {
+            Ok(()) => done.push(mv.clone()),
+            Err(e) => errors.push(format!("{}: {e}", mv.from.display())),
+        }
+    }
+    if !done.is_empty() {
+        coll.move_journal.push(MoveBatch {
+            moves: done,
+            created_dirs,
+        });
+        if coll.move_journal.len() > MAX_JOURNAL_LEN {
+            coll.move_journal.remove(0);
+        }
+    }
+    errors
+}
+
+/// Move the files of the last batch in the journal back to where they were.
+///
+/// Returns the errors of the moves that couldn't be undone.
+pub fn undo_last(coll: &mut Collection) -> anyhow::Result<Vec<String>> {
+    let Some(batch) = coll.move_journal.pop() else {
+        bail!("Nothing to undo");
+    };
+    let mut errors = Vec::new();
+    for mv in batch.moves.iter().rev() {
+        if let Err(e) = move_entry(coll, mv.id, &mv.to, &mv.from, &mut Vec::new()) {
+            errors.push(format!("{}: {e}", mv.to.display()));
+        }
+    }
+    // Innermost first. Folders that aren't empty (anymore) are left alone.
+    for dir in batch.created_dirs.iter().rev() {
+        let _ = std::fs::remove_dir(dir);
+    }
+    Ok(errors)
+}
+
+/// Move an entry's file, adding the folders that had to be created to `created_dirs`, outermost
+/// first
+fn move_entry(
+    coll: &mut Collection,
+    id: entry::Id,
+    from: &Path,
+    to: &Path,
+    created_dirs: &mut Vec<PathBuf>,
+) -> anyhow::Result<()> {
+    let Some(en) = coll.entries.get_mut(&id) else {
+        bail!("Entry no longer exists");
+    };
+    if en.path != from {
+        bail!("Entry was moved in the meantime");
+    }
+    // Could have appeared since planning
+    if to.exists() {
+        bail!("Destination {} already exists", to.display());
+    }
+    if let Some(parent) = to.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        let missing: Vec<&Path> = parent
+            .ancestors()
+            .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+            .collect();
+        std::fs::create_dir_all(parent)?;
+        created_dirs.extend(missing.into_iter().rev().map(Path::to_path_buf));
+    }
+    std::fs::rename(from, to)?;
+    en.path = to.to_owned();
+    Ok(())
+}
+
+pub(crate) fn is_inside_root(folder: &Path) -> bool {
+    folder
+        .components()
+        .all(|comp| matches!(comp, Component::Normal(_) | Component::CurDir))
+}
+
+/// `path` with ` (n)` added to the file stem, with the lowest n >= 2 that isn't taken
+pub(crate) fn numbered_free_path(path: &Path, is_taken: impl Fn(&Path) -> bool) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().map(|ext| ext.to_string_lossy());
+    let mut n = 2;
+    loop {
+        let name = match &ext {
+            Some(ext) => format!("{stem} ({n}).{ext}"),
+            None => format!("{stem} ({n})"),
+        };
+        let candidate = path.with_file_name(name);
+        if !is_taken(&candidate) {
+            return candidate;
+        }
+        n += 1;
+    }
+}
@@ -0,0 +1,117 @@
+//! Perceptual hashing, for finding resized, re-encoded or slightly cropped copies of an image
+
+use {
+    crate::{archive, collection, db::EntryMap, dlog, entry, exif_meta},
+    image::DynamicImage,
+    parking_lot::Mutex,
+    serde_derive::{Deserialize, Serialize},
+    std::{
+        num::NonZero,
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        thread::JoinHandle,
+        time::SystemTime,
+    },
+};
+
+/// Hashes at most this many bits apart are considered similar by default
+pub const DEFAULT_MAX_DIST: u32 = 10;
+
+/// A cached hash, along with the modification time of the file when it was hashed.
+///
+/// If the file's modification time changes, the hash is out of date.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct StoredHash {
+    pub hash: u64,
+    pub mtime: Option<SystemTime>,
+}
+
+/// Difference hash (dHash) of `img`.
+///
+/// Each bit tells whether a pixel of a 9x8 grayscale downscale is darker than its right neighbor.
+pub fn dhash(img: &DynamicImage) -> u64 {
+    let small = img.thumbnail_exact(9, 8).to_luma8();
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = small.get_pixel(x, y).0[0];
+            let right = small.get_pixel(x + 1, y).0[0];
+            hash = (hash << 1) | u64::from(left < right);
+        }
+    }
+    hash
+}
+
+pub fn hash_file(path: &Path) -> image::ImageResult<u64> {
+    let data = archive::read(path)?;
+    Ok(dhash(&exif_meta::load_oriented(&data)?))
+}
+
+/// Number of differing bits between two hashes
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Hashes the files of entries that don't have a hash yet, in the background
+pub struct HashScan {
+    /// The collection the entries belong to
+    pub coll_id: collection::Id,
+    pub hashed: Arc<AtomicUsize>,
+    pub total: usize,
+    handle: Option<JoinHandle<EntryMap<StoredHash>>>,
+}
+
+impl HashScan {
+    /// Start hashing `files`. The paths should be absolute, the working directory can change.
+    pub fn start(coll_id: collection::Id, files: Vec<(entry::Id, PathBuf)>) -> Self {
+        let hashed = Arc::new(AtomicUsize::new(0));
+        let hashed_clone = Arc::clone(&hashed);
+        let total = files.len();
+        let handle = std::thread::spawn(move || {
+            let hashes = Mutex::new(EntryMap::default());
+            let n_threads = std::thread::available_parallelism().map_or(1, NonZero::get);
+            let chunk_size = files.len().div_ceil(n_threads).max(1);
+            std::thread::scope(|s| {
+                for chunk in files.chunks(chunk_size) {
+                    let hashes = &hashes;
+                    let hashed = &hashed_clone;
+                    s.spawn(move || {
+                        for (id, path) in chunk {
+                            // Taken before reading, so a write during hashing is noticed later
+                            let mtime = archive::mtime(path);
+                            match hash_file(path) {
+                                Ok(hash) => {
+                                    hashes.lock().insert(*id, StoredHash { hash, mtime });
+                                }
+                                Err(e) => dlog!("Failed to hash {path:?}: {e}"),
+                            }
+                            hashed.fetch_add(1, Ordering::Relaxed);
+                        }
+                    });
+                }
+            });
+            hashes.into_inner()
+        });
+        Self {
+            coll_id,
+            hashed,
+            total,
+            handle: Some(handle),
+        }
+    }
+    /// Returns the hashes once the scan is done
+    pub fn poll(&mut self) -> Option<anyhow::Result<EntryMap<StoredHash>>> {
+        if !self.handle.as_ref()?.is_finished() {
+            return None;
+        }
+        let handle = self.handle.take()?;
+        Some(
+            handle
+                .join()
+                .map_err(|_| anyhow::anyhow!("Hashing thread panicked")),
+        )
+    }
+}
@@ -40,7 +40,42 @@ pub struct Preferences {
     #[serde(default = "thumbs_per_row_default")]
     pub thumbs_per_row: u8,
     #[serde(default)]
+    pub thumbnail_layout: ThumbnailLayout,
+    #[serde(default)]
     pub color_theme: Option<ColorTheme>,
+    /// Watch the active collection's folder for changes
+    #[serde(default = "watch_folder_default")]
+    pub watch_folder: bool,
+    #[serde(default = "ThumbnailThreads::default")]
+    pub thumbnail_threads: u8,
+    /// Memory budget for thumbnail textures, in MiB
+    #[serde(default = "ThumbnailCacheBudget::default")]
+    pub thumbnail_cache_mib: u16,
+    /// Play animated images in the thumbnails view when hovering over them
+    #[serde(default = "animate_on_hover_default")]
+    pub animate_thumbnails_on_hover: bool,
+}
+
+/// How the thumbnails view arranges the thumbnails
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ThumbnailLayout {
+    /// Square cells, `thumbs_per_row` of them per row
+    #[default]
+    Grid,
+    /// Rows of thumbnails keeping their aspect ratios, stretched to fill the width.
+    ///
+    /// Rows are about as tall as the grid cells would be.
+    JustifiedRows,
+}
+
+impl ThumbnailLayout {
+    pub const ALL: [Self; 2] = [Self::Grid, Self::JustifiedRows];
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Grid => "Grid",
+            Self::JustifiedRows => "Justified rows",
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -64,6 +99,14 @@ const fn built_in_viewer_default() -> bool {
     true
 }
 
+const fn watch_folder_default() -> bool {
+    true
+}
+
+const fn animate_on_hover_default() -> bool {
+    true
+}
+
 const fn thumbs_per_row_default() -> u8 {
     5
 }
@@ -146,6 +189,22 @@ impl ValuePref for ThumbnailsPerRow {
     const NAME: &'static str = "Thumbnails per row";
 }
 
+pub enum ThumbnailThreads {}
+impl ValuePref for ThumbnailThreads {
+    type Type = u8;
+    const DEFAULT: u8 = 4;
+    const RANGE: RangeInclusive<u8> = 1..=32;
+    const NAME: &'static str = "Thumbnail loading threads";
+}
+
+pub enum ThumbnailCacheBudget {}
+impl ValuePref for ThumbnailCacheBudget {
+    type Type = u16;
+    const DEFAULT: u16 = 512;
+    const RANGE: RangeInclusive<u16> = 32..=8192;
+    const NAME: &'static str = "Thumbnail memory budget (MiB)";
+}
+
 impl Default for Preferences {
     fn default() -> Self {
         Self {
@@ -158,7 +217,12 @@ impl Default for Preferences {
             use_built_in_viewer: true,
             start_fullscreen: false,
             thumbs_per_row: thumbs_per_row_default(),
+            thumbnail_layout: ThumbnailLayout::default(),
             color_theme: None,
+            watch_folder: watch_folder_default(),
+            thumbnail_threads: ThumbnailThreads::DEFAULT,
+            thumbnail_cache_mib: ThumbnailCacheBudget::DEFAULT,
+            animate_thumbnails_on_hover: animate_on_hover_default(),
         }
     }
 }